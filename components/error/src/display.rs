@@ -1,163 +1,321 @@
-// use html::HTMLError;
-
-use crate::AstryxError;
-use parser::{error::ParserErrorKind, ParserError, Span};
-
-// pub type AstryxResult<'a, T> = Result<T, AstryxError<'a>>;
-
-// #[derive(Debug)]
-// pub enum AstryxError<'a> {
-//     ParserError(ParserError<Span<'a>>),
-//     InterpreterError(InterpreterError),
-//     // HTMLError,
-//     IO(std::io::Error),
-// }
-
-// impl<'a> From<ParserError<Span<'a>>> for AstryxError<'a> {
-//     fn from(e: ParserError<Span<'a>>) -> AstryxError<'a> {
-//         AstryxError::ParserError(e)
-//     }
-// }
-
-// impl<'a> From<InterpreterError> for AstryxError<'a> {
-//     fn from(e: InterpreterError) -> AstryxError<'a> {
-//         AstryxError::InterpreterError(e)
-//     }
-// }
-
-// impl<'a> From<HTMLError> for AstryxError<'a> {
-//     fn from(_e: HTMLError) -> AstryxError<'a> {
-//         AstryxError::HTMLError
-//     }
-// }
-
-// impl<'a> From<std::io::Error> for AstryxError<'a> {
-//     fn from(e: std::io::Error) -> AstryxError<'a> {
-//         AstryxError::IO(e)
-//     }
-// }
+// terminal and plain text views of errors
+//
+//     error[E0102]: unknown variable `titel`
+//      --> site.astryx:3:5
+//       |
+//     3 |     titel
+//       |     ^^^^^ not found in this scope
+//       |
+//     1 | let title = "hello"
+//       |     ----- `title` defined here
+//       |
+//       = help: did you mean `title`?
+
+use crate::{AstryxError, Diagnostic, Location};
+use serde_json::json;
+use std::io::IsTerminal;
+
+const TAB_WIDTH: usize = 4;
+
+/// ansi escapes for each part of an error, all empty for plain text
+struct Style {
+    error: &'static str,
+    secondary: &'static str,
+    gutter: &'static str,
+    bold: &'static str,
+    reset: &'static str,
+}
+
+const PLAIN: Style = Style {
+    error: "",
+    secondary: "",
+    gutter: "",
+    bold: "",
+    reset: "",
+};
+
+const COLOURED: Style = Style {
+    error: "\x1b[1;31m",
+    secondary: "\x1b[1;34m",
+    gutter: "\x1b[1;34m",
+    bold: "\x1b[1m",
+    reset: "\x1b[0m",
+};
 
 pub fn html_error_page(content: &str) -> String {
-    format!("<html style='background-color: black;color: white;'><body><h1>Error :(</h1><pre>{}</pre></body></html>", content)
+    format!(
+        "<html style='background-color: black;color: white;'><body><h1>Error :(</h1><pre>{}</pre></body></html>",
+        content
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    )
 }
 
 /// convert an error to a display-friendly string
 pub fn display_error(err: &AstryxError, path: &str) -> String {
+    render(err, path, &PLAIN)
+}
+
+/// `display_error`, coloured when stderr is a terminal (unless `NO_COLOR` is set)
+pub fn terminal_error(err: &AstryxError, path: &str) -> String {
+    let coloured = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    render(err, path, if coloured { &COLOURED } else { &PLAIN })
+}
+
+/// an error as a single line json object, for tools. errors without a position in the
+/// source have a `null` line, column, length and code.
+pub fn json_error(err: &AstryxError, path: &str) -> String {
+    let value = match err {
+        AstryxError::LocatedError(d) => json!({
+            "file": match d.location.filename() {
+                "" => path,
+                filename => filename,
+            },
+            "line": d.location.line,
+            "column": d.location.column,
+            "length": d.location.length,
+            "severity": "error",
+            "code": d.kind.code(),
+            "message": d.kind.message(),
+            "help": d.help,
+        }),
+        AstryxError::Generic(message) => unlocated_json(path, message),
+        AstryxError::IO(e) => unlocated_json(path, &e.to_string()),
+    };
+
+    value.to_string()
+}
+
+fn unlocated_json(path: &str, message: &str) -> serde_json::Value {
+    json!({
+        "file": path,
+        "line": null,
+        "column": null,
+        "length": null,
+        "severity": "error",
+        "code": null,
+        "message": message,
+        "help": null,
+    })
+}
+
+fn render(err: &AstryxError, path: &str, style: &Style) -> String {
     match err {
-        AstryxError::LocatedError(l, kind) => error_with_line(
-            &l.context,
-            l.line,
-            l.column,
-            l.length,
-            &format!("{:?}", kind),
-            &l.filename,
+        AstryxError::LocatedError(d) => diagnostic(d, path, style),
+        AstryxError::Generic(e) => format!("{}error{}: {}", style.error, style.reset, e),
+        AstryxError::IO(e) => format!("{}error{}: {}", style.error, style.reset, e),
+    }
+}
+
+fn diagnostic(d: &Diagnostic, path: &str, style: &Style) -> String {
+    let filename = match d.location.filename() {
+        "" => path,
+        filename => filename,
+    };
+
+    // wide enough for every line number shown
+    let width = std::iter::once(&d.location)
+        .chain(d.labels.iter().map(|(location, _)| location))
+        .map(|location| location.line.to_string().len())
+        .max()
+        .unwrap_or(1);
+
+    let gutter = format!(
+        "{}{:width$} |{}",
+        style.gutter,
+        "",
+        style.reset,
+        width = width
+    );
+
+    let mut lines = vec![
+        format!(
+            "{}error[{}]{}{}: {}{}",
+            style.error,
+            d.kind.code(),
+            style.reset,
+            style.bold,
+            d.kind.message(),
+            style.reset
+        ),
+        format!(
+            "{}{:width$}--> {}{}:{}:{}",
+            style.gutter,
+            "",
+            style.reset,
+            filename,
+            d.location.line,
+            d.location.column,
+            width = width
         ),
-        AstryxError::Generic(e) => format!("Generic: {:?}", e),
-        AstryxError::IO(e) => format!("IO {:?}", e),
+        gutter.clone(),
+    ];
+
+    lines.extend(snippet(
+        &d.location,
+        '^',
+        d.kind.label(),
+        style.error,
+        width,
+        style,
+    ));
+
+    for (location, message) in &d.labels {
+        lines.push(gutter.clone());
+        lines.extend(snippet(
+            location,
+            '-',
+            message,
+            style.secondary,
+            width,
+            style,
+        ));
     }
 
-    // match &err {
-    //     AstryxError::ParserError(e) => format!(
-    //         "syntax error: {}",
-    //         error_with_line(
-    //             &e.context.to_string(),
-    //             e.pos.location_line(),
-    //             e.pos.get_column() as usize,
-    //             e.pos.location_offset() as usize,
-    //             &parser_reason(&e.kind),
-    //             path
-    //         )
-    //     ),
-    //     AstryxError::InterpreterError(e) => {
-    //         let location = e.clone().location.unwrap();
-    //         format!(
-    //             "logical error: {}",
-    //             error_with_line(
-    //                 &get_line_from_file(path, location.line as usize),
-    //                 location.line,
-    //                 location.column,
-    //                 location.length,
-    //                 &interpreter_reason(&e.kind),
-    //                 path
-    //             )
-    //         )
-    //     }
-    //     // AstryxError::HTMLError => format!("HTMLError"),
-    //     AstryxError::IO(_) => format!("IO"),
-    // }
-}
+    if let Some(help) = &d.help {
+        lines.push(gutter);
+        lines.push(format!(
+            "{}{:width$} ={} {}help{}: {}",
+            style.gutter,
+            "",
+            style.reset,
+            style.bold,
+            style.reset,
+            help,
+            width = width
+        ));
+    }
 
-fn get_line_from_file<'a>(path: &str, line: usize) -> String {
-    std::fs::read_to_string(&path)
-        .map(|file| file.lines().map(String::from).collect::<Vec<String>>()[line].clone())
-        .unwrap_or(String::new())
+    lines.join("\n")
 }
 
-fn parser_reason(kind: &ParserErrorKind<Span>) -> String {
-    format!("{:?}", kind)
-}
+/// a source line with the span at `location` underlined by `mark` and labelled
+fn snippet(
+    location: &Location,
+    mark: char,
+    label: &str,
+    colour: &str,
+    width: usize,
+    style: &Style,
+) -> Vec<String> {
+    let source = source_line(location);
+    let before: String = source
+        .chars()
+        .take(location.column.saturating_sub(1))
+        .collect();
 
-// fn interpreter_reason(kind: &InterpreterErrorKind) -> String {
-//     match &kind {
-//         InterpreterErrorKind::NoWriter => {
-//             format!("cannot write to output without a specified file or stdout target.")
-//         }
-//         InterpreterErrorKind::Unhandled => format!("unhandler interpreter error."),
-//         InterpreterErrorKind::Generic(e) => format!("{:?}", e),
-//         InterpreterErrorKind::FunctionNotFound(f) => format!("function not found {:?}", f),
-//         InterpreterErrorKind::ReferenceIsNotAFunction => format!("ReferenceIsNotAFunction"),
-//         InterpreterErrorKind::InvalidReference(r) => format!("invalid reference: {}", r),
-//         InterpreterErrorKind::UnexpectedToken { expected, got } => {
-//             format!("expected {}, got {}", expected, got)
-//         }
-//         InterpreterErrorKind::IOError => format!("error reading file"),
-//         InterpreterErrorKind::UnknownMemberFunction(mf) => {
-//             format!("missing member function: {}", mf)
-//         }
-//     }
-// }
-
-// terminal view for errors
-fn error_with_line(
-    context: &str,
-    line: u32,
-    col: usize,
-    length: usize,
-    reason: &str,
-    path: &str,
-) -> String {
-    // panic!("context: {:?}", context.fragment());
-    [
-        format!("{}", reason),
-        format!("--> {}:{}:{}", path, line, col),
-        String::from("  |"),
-        format!("{} | {}", line, context), //file.lines().into_iter().enumerate().collect::<Vec<String>>()[context.location_line() as usize]),
+    let marked: String = source
+        .chars()
+        .skip(location.column.saturating_sub(1))
+        .take(location.length)
+        .collect();
+
+    let indent = display_width(&before);
+    let marks = mark
+        .to_string()
+        .repeat(display_width(&marked).max(location.length).max(1));
+
+    vec![
+        format!(
+            "{}{:>width$} |{} {}",
+            style.gutter,
+            location.line,
+            style.reset,
+            source.replace('\t', &" ".repeat(TAB_WIDTH)),
+            width = width
+        ),
         format!(
-            "  |{space:column$}{caret:offset$}",
-            space = " ",
-            caret = "^",
-            column = col,
-            offset = length,
+            "{}{:width$} |{} {:indent$}{}{}{}",
+            style.gutter,
+            "",
+            style.reset,
+            "",
+            colour,
+            format!("{} {}", marks, label).trim_end(),
+            style.reset,
+            width = width,
+            indent = indent
         ),
     ]
-    .join("\n")
 }
 
-// // terminal view for errors
-// fn error_with_line(context: &Span, pos: &Span, reason: &str, path: &str) -> String {
-//     // panic!("context: {:?}", context.fragment());
-//     [
-//         format!("{}", reason),
-//         format!("--> {}:{}:{}", path, pos.location_line(), pos.get_column()),
-//         String::from("  |"),
-//         format!("{} | {}", context.location_line(), context), //file.lines().into_iter().enumerate().collect::<Vec<String>>()[context.location_line() as usize]),
-//         format!(
-//             "  |{space:column$}{caret:offset$}",
-//             space = " ",
-//             caret = "^",
-//             column = pos.get_column(),
-//             offset = pos.location_offset(),
-//         ),
-//     ]
-//     .join("\n")
-// }
+/// the whole line an error is on, from its file if it can be read. spans only hold
+/// the line up to the end of the error, which is used otherwise.
+fn source_line(location: &Location) -> String {
+    std::fs::read_to_string(location.filename())
+        .ok()
+        .and_then(|file| {
+            file.lines()
+                .nth(location.line.saturating_sub(1) as usize)
+                .map(String::from)
+        })
+        .filter(|line| line.starts_with(location.context.trim_end()))
+        .unwrap_or_else(|| location.context.trim_end().to_string())
+}
+
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::AstryxErrorKind;
+    use nom::Slice;
+    use parser::Span;
+
+    #[test]
+    fn test_display_error() {
+        let source = "let title = \"hello\"\n%p ${titel}";
+        let file = Span::new_extra(source, "site.astryx");
+        let (definition, usage) = (file.slice(4..9), file.slice(25..30));
+
+        let error = AstryxError::with_loc(usage, AstryxErrorKind::UnknownVariable("titel".into()))
+            .with_label(definition, "`title` defined here")
+            .with_help(String::from("did you mean `title`?"));
+
+        assert_eq!(
+            display_error(&error, "site.astryx"),
+            [
+                "error[E0102]: unknown variable `titel`",
+                " --> site.astryx:2:6",
+                "  |",
+                "2 | %p ${titel",
+                "  |      ^^^^^ not found in this scope",
+                "  |",
+                "1 | let title",
+                "  |     ----- `title` defined here",
+                "  |",
+                "  = help: did you mean `title`?",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_json_error() {
+        let source = "%p ${titel}";
+        let usage = Span::new_extra(source, "").slice(5..10);
+        let error = AstryxError::with_loc(usage, AstryxErrorKind::UnknownVariable("titel".into()));
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json_error(&error, "site.astryx")).unwrap(),
+            json!({
+                "file": "site.astryx",
+                "line": 1,
+                "column": 6,
+                "length": 5,
+                "severity": "error",
+                "code": "E0102",
+                "message": "unknown variable `titel`",
+                "help": null,
+            })
+        );
+
+        let error = AstryxError::Generic(String::from("no routes"));
+        assert!(json_error(&error, "site.astryx").contains("\"message\":\"no routes\""));
+    }
+}
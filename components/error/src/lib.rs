@@ -1,20 +1,78 @@
-use parser::{ParserError, Span};
+use parser::{error::ParserErrorKind, ParserError, Span};
 
 pub mod display;
+mod suggest;
+
+pub use suggest::suggest;
 
 pub type AstryxResult<T> = Result<T, AstryxError>;
 
+/// the filename of spans made up by builtin functions, which are moved to where the
+/// function was called by `at_call`
+pub const BUILTIN: &str = "<builtin>";
+
 #[derive(Debug)]
 pub enum AstryxError {
-    LocatedError(Location, AstryxErrorKind),
+    LocatedError(Box<Diagnostic>),
     Generic(String),
     // HTMLError,
     IO(std::io::Error),
 }
 
+/// an error at a position in a source file, with anything else worth pointing out
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub location: Location,
+    pub kind: AstryxErrorKind,
+    /// other positions involved, like where a variable was defined
+    pub labels: Vec<(Location, String)>,
+    /// a suggestion for fixing the error
+    pub help: Option<String>,
+}
+
 impl AstryxError {
     pub fn with_loc<L: Into<Location>>(loc: L, kind: AstryxErrorKind) -> Self {
-        AstryxError::LocatedError(loc.into(), kind)
+        AstryxError::LocatedError(Box::new(Diagnostic {
+            location: loc.into(),
+            kind,
+            labels: Vec::new(),
+            help: None,
+        }))
+    }
+
+    /// point out another position involved in a located error
+    pub fn with_label<L: Into<Location>>(mut self, loc: L, message: &str) -> Self {
+        if let AstryxError::LocatedError(diagnostic) = &mut self {
+            diagnostic.labels.push((loc.into(), message.into()));
+        }
+        self
+    }
+
+    /// give an error from a builtin function the position it was called from, unless it
+    /// already points somewhere in the source
+    pub fn at_call<L: Into<Location>>(self, loc: L) -> Self {
+        match self {
+            AstryxError::LocatedError(mut diagnostic) => {
+                if diagnostic.location.filename == BUILTIN {
+                    diagnostic.location = loc.into();
+                }
+                AstryxError::LocatedError(diagnostic)
+            }
+            AstryxError::Generic(message) => {
+                AstryxError::with_loc(loc, AstryxErrorKind::FunctionError(message))
+            }
+            AstryxError::IO(e) => {
+                AstryxError::with_loc(loc, AstryxErrorKind::FunctionError(e.to_string()))
+            }
+        }
+    }
+
+    /// add a suggestion to a located error
+    pub fn with_help(mut self, help: String) -> Self {
+        if let AstryxError::LocatedError(diagnostic) = &mut self {
+            diagnostic.help = Some(help);
+        }
+        self
     }
 }
 
@@ -23,12 +81,16 @@ impl std::error::Error for AstryxError {}
 impl std::fmt::Display for AstryxError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
-            // DoubleError::EmptyVec => write!(f, "please use a vector with at least one element"),
-            // // The wrapped error contains additional information and is available
-            // // via the source() method.
-            // DoubleError::Parse(..) => write!(f, "the provided string could not be parsed as int"),
-            AstryxError::LocatedError(_, _) => write!(f, "error at:"),
-            AstryxError::IO(_) => write!(f, "io error"),
+            AstryxError::LocatedError(ref d) => write!(
+                f,
+                "error[{}]: {} at {}:{}:{}",
+                d.kind.code(),
+                d.kind.message(),
+                d.location.filename,
+                d.location.line,
+                d.location.column
+            ),
+            AstryxError::IO(ref e) => write!(f, "io error: {}", e),
             AstryxError::Generic(ref msg) => write!(f, "generic error: {}", msg),
         }
     }
@@ -42,33 +104,177 @@ pub enum AstryxErrorKind {
     Unexpected,
     UnexpectedToken(String),
     ExpectedValue,
+    InconsistentIndentation {
+        unit: String,
+        found: String,
+    },
+    OverIndented,
+    UnclosedComment,
     UnknownValue(String),
+    UnknownVariable(String),
+    UnknownKey(String),
+    NotAFunction(String),
+    MismatchedType {
+        expected: String,
+        found: String,
+    },
+    /// an error from inside a builtin function
+    FunctionError(String),
+    ReadError {
+        path: String,
+        reason: String,
+    },
+    InvalidGlob(String),
+    DuplicateRoute(String),
+}
+
+impl AstryxErrorKind {
+    /// a stable code for each kind of error, `E00xx` for syntax and `E01xx` for evaluation
+    pub fn code(&self) -> &'static str {
+        match self {
+            AstryxErrorKind::SyntaxError => "E0001",
+            AstryxErrorKind::Unexpected => "E0002",
+            AstryxErrorKind::UnexpectedToken(_) => "E0003",
+            AstryxErrorKind::ExpectedValue => "E0004",
+            AstryxErrorKind::InconsistentIndentation { .. } => "E0005",
+            AstryxErrorKind::OverIndented => "E0006",
+            AstryxErrorKind::UnclosedComment => "E0007",
+            AstryxErrorKind::FunctionArgumentError => "E0101",
+            AstryxErrorKind::UnknownVariable(_) => "E0102",
+            AstryxErrorKind::UnknownKey(_) => "E0103",
+            AstryxErrorKind::NotAFunction(_) => "E0104",
+            AstryxErrorKind::MissingRequiredArgument(_) => "E0105",
+            AstryxErrorKind::UnknownValue(_) => "E0106",
+            AstryxErrorKind::MismatchedType { .. } => "E0107",
+            AstryxErrorKind::FunctionError(_) => "E0108",
+            AstryxErrorKind::ReadError { .. } => "E0109",
+            AstryxErrorKind::InvalidGlob(_) => "E0110",
+            AstryxErrorKind::DuplicateRoute(_) => "E0111",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AstryxErrorKind::SyntaxError => String::from("syntax error"),
+            AstryxErrorKind::Unexpected => String::from("unexpected input"),
+            AstryxErrorKind::UnexpectedToken(t) => format!("unexpected `{}`", t),
+            AstryxErrorKind::ExpectedValue => String::from("expected a value"),
+            AstryxErrorKind::InconsistentIndentation { unit, found } => format!(
+                "indented with {}, but each level of indentation in this file is {}",
+                found, unit
+            ),
+            AstryxErrorKind::OverIndented => {
+                String::from("indented more than one level past the line above")
+            }
+            AstryxErrorKind::UnclosedComment => String::from("block comment is never closed"),
+            AstryxErrorKind::FunctionArgumentError => String::from("invalid function arguments"),
+            AstryxErrorKind::UnknownVariable(v) => format!("unknown variable `{}`", v),
+            AstryxErrorKind::UnknownKey(k) => format!("no key called `{}`", k),
+            AstryxErrorKind::NotAFunction(f) => format!("`{}` is not a function", f),
+            AstryxErrorKind::MissingRequiredArgument(a) => {
+                format!("missing required argument `{}`", a)
+            }
+            AstryxErrorKind::UnknownValue(v) => format!("unknown value `{}`", v),
+            AstryxErrorKind::MismatchedType { expected, found } => {
+                format!("expected {}, found {}", expected, found)
+            }
+            AstryxErrorKind::FunctionError(e) => e.clone(),
+            AstryxErrorKind::ReadError { path, reason } => {
+                format!("could not read `{}`: {}", path, reason)
+            }
+            AstryxErrorKind::InvalidGlob(e) => format!("invalid glob pattern: {}", e),
+            AstryxErrorKind::DuplicateRoute(r) => format!("more than one route for `{}`", r),
+        }
+    }
+
+    /// the label for the primary position
+    pub fn label(&self) -> &'static str {
+        match self {
+            AstryxErrorKind::UnknownVariable(_) => "not found in this scope",
+            AstryxErrorKind::UnknownKey(_) => "not found",
+            AstryxErrorKind::NotAFunction(_) => "called here",
+            AstryxErrorKind::MissingRequiredArgument(_) => "required here",
+            AstryxErrorKind::MismatchedType { .. } => "wrong type",
+            AstryxErrorKind::FunctionError(_) => "in this call",
+            AstryxErrorKind::DuplicateRoute(_) => "defined again here",
+            _ => "",
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Location {
     pub line: u32,
     pub column: usize,
+    /// in characters
     pub length: usize,
     filename: String,
     context: String,
 }
 
+impl Location {
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+}
+
 impl<'a> From<Span<'a>> for Location {
     fn from(span: Span) -> Self {
         Self {
             line: span.location_line(),
-            column: span.get_column(),
-            length: span.location_offset(),
+            column: span.get_utf8_column(),
+            length: span.fragment().chars().count(),
             filename: span.extra.into(),
-            context: String::from_utf8(span.get_line_beginning().into()).unwrap(),
+            context: String::from_utf8_lossy(span.get_line_beginning()).into(),
         }
     }
 }
 
 impl<'a> From<ParserError<Span<'a>>> for AstryxError {
     fn from(e: ParserError<Span<'a>>) -> AstryxError {
-        AstryxError::LocatedError(e.pos.into(), AstryxErrorKind::Unexpected) // FIXME
+        let kind = match e.kind {
+            ParserErrorKind::SyntaxError => AstryxErrorKind::SyntaxError,
+            ParserErrorKind::FunctionArgumentError => AstryxErrorKind::FunctionArgumentError,
+            ParserErrorKind::UnexpectedToken(t) => AstryxErrorKind::UnexpectedToken(t),
+            ParserErrorKind::ExpectedValue => AstryxErrorKind::ExpectedValue,
+            ParserErrorKind::InconsistentIndentation { unit, found } => {
+                AstryxErrorKind::InconsistentIndentation { unit, found }
+            }
+            ParserErrorKind::OverIndented => AstryxErrorKind::OverIndented,
+            ParserErrorKind::UnclosedComment => AstryxErrorKind::UnclosedComment,
+            ParserErrorKind::Unexpected | ParserErrorKind::Nom(_) => AstryxErrorKind::Unexpected,
+        };
+
+        // the position is the rest of the input, so only its first word is marked (or all of
+        // the indentation, for indentation errors)
+        let mut location = Location::from(e.pos);
+        location.length = match kind {
+            AstryxErrorKind::InconsistentIndentation { .. } => location.length,
+            _ => e
+                .pos
+                .fragment()
+                .split_whitespace()
+                .next()
+                .map_or(1, |word| word.chars().count()),
+        };
+
+        let help = match &kind {
+            AstryxErrorKind::InconsistentIndentation { unit, .. } => {
+                Some(format!("indent each level with {}", unit))
+            }
+            AstryxErrorKind::OverIndented => {
+                Some(String::from("indent a line one level past its parent"))
+            }
+            AstryxErrorKind::UnclosedComment => Some(String::from("end the comment with `]--`")),
+            _ => None,
+        };
+
+        AstryxError::LocatedError(Box::new(Diagnostic {
+            location,
+            kind,
+            labels: Vec::new(),
+            help,
+        }))
     }
 }
 
@@ -77,3 +283,52 @@ impl<'a> From<std::io::Error> for AstryxError {
         AstryxError::IO(e)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let span = Span::new_extra("let title = 1", "site.astryx");
+        let location = Location::from(span);
+        assert_eq!(location.length, 13);
+        assert_eq!(location.column, 1);
+        assert_eq!(location.filename(), "site.astryx");
+    }
+
+    #[test]
+    fn test_at_call() {
+        let call = Span::new_extra("markdown()", "site.astryx");
+
+        let missing = AstryxError::with_loc(
+            Span::new_extra("path", BUILTIN),
+            AstryxErrorKind::MissingRequiredArgument(String::from("path")),
+        );
+        match missing.at_call(call) {
+            AstryxError::LocatedError(d) => assert_eq!(d.location.filename(), "site.astryx"),
+            e => panic!("expected a located error, got {:?}", e),
+        }
+
+        match AstryxError::Generic(String::from("oops")).at_call(call) {
+            AstryxError::LocatedError(d) => {
+                assert_eq!(d.kind, AstryxErrorKind::FunctionError(String::from("oops")))
+            }
+            e => panic!("expected a located error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_indentation_error() {
+        let (_, mut errors) = parser::run_all("%a\n\t%b\n  %c\n", "site.astryx");
+
+        match AstryxError::from(errors.remove(0)) {
+            AstryxError::LocatedError(d) => {
+                assert_eq!(d.kind.code(), "E0005");
+                assert_eq!((d.location.line, d.location.length), (3, 2));
+                assert_eq!(d.help, Some(String::from("indent each level with a tab")));
+            }
+            e => panic!("expected a located error, got {:?}", e),
+        }
+    }
+}
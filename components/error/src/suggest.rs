@@ -0,0 +1,69 @@
+// "did you mean" suggestions for misspelled names
+
+/// the candidate closest to `name`, if any is close enough to be a likely typo
+pub fn suggest<'a, I: IntoIterator<Item = &'a str>>(name: &str, candidates: I) -> Option<&'a str> {
+    // roughly one typo for every three characters
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// levenshtein distance, counting a swap of two neighbouring characters as one edit
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    // rows[i][j] is the distance between a[..i] and b[..j]
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut d = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+
+            rows[i][j] = d;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("title", "title"), 0);
+        assert_eq!(distance("titel", "title"), 1);
+        assert_eq!(distance("markdwn", "markdown"), 1);
+        assert_eq!(distance("a", "xyz"), 3);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let names = vec!["title", "date", "markdown", "toc"];
+        assert_eq!(suggest("titel", names.clone()), Some("title"));
+        assert_eq!(suggest("markdwon", names.clone()), Some("markdown"));
+        assert_eq!(suggest("tco", names.clone()), Some("toc"));
+        assert_eq!(suggest("author", names), None);
+    }
+}
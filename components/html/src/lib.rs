@@ -38,8 +38,11 @@ impl HTMLElement {
 }
 
 fn attributes_to_string(attributes: &Attributes) -> String {
-    // format attributes
+    // format attributes, sorted by name so a page renders the same every time
     if !attributes.is_empty() {
+        let mut attributes: Vec<(&String, &String)> = attributes.iter().collect();
+        attributes.sort();
+
         format!(
             " {}",
             attributes
@@ -1,6 +1,7 @@
-use error::{AstryxError, AstryxResult};
+use error::{AstryxError, AstryxErrorKind, AstryxResult, BUILTIN};
+use markdown::{HeadingNode, HighlightStyle, HighlighterOptions, MarkdownOptions};
 use models::{object::Object, state::State};
-use parser::Span;
+use parser::{Expression, Span, Statement};
 use rctree::Node;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -25,6 +26,20 @@ pub fn import(state: Rc<RefCell<State>>) -> Rc<RefCell<State>> {
     //     .borrow_mut()
     //     .bind("page", Object::BuiltinFunction(page));
 
+    let _ = state.borrow_mut().bind("toc", Object::BuiltinFunction(toc));
+
+    let _ = state
+        .borrow_mut()
+        .bind("load", Object::BuiltinFunction(load));
+
+    let _ = state
+        .borrow_mut()
+        .bind("callout", Object::BuiltinFunction(callout));
+
+    let _ = state
+        .borrow_mut()
+        .bind("syntaxcss", Object::BuiltinFunction(syntax_css));
+
     let _ = state
         .borrow_mut()
         .bind("asset", Object::BuiltinFunction(asset));
@@ -39,11 +54,11 @@ pub fn import(state: Rc<RefCell<State>>) -> Rc<RefCell<State>> {
 pub(crate) fn log(state: Rc<RefCell<State>>, input: Option<Node<Object>>) -> AstryxResult<Object> {
     match input {
         Some(input) => {
-            println!("{:?}", input.borrow().to_string());
+            eprintln!("{:?}", input.borrow().to_string());
             Ok(input.borrow().clone())
         }
         None => {
-            println!(
+            eprintln!(
                 "{:?}",
                 state
                     .borrow()
@@ -77,12 +92,228 @@ pub(crate) fn markdown<'a>(
 ) -> AstryxResult<Object> {
     let path = match input {
         Some(input) => input.borrow().clone(),
-        None => state.borrow().require(Span::new_extra("path", "error"))?,
+        None => state.borrow().require(Span::new_extra("path", BUILTIN))?,
+    };
+
+    let options = markdown_options(&state.borrow())?;
+    let (_, body) = read_frontmatter(Rc::clone(&state), path)?;
+
+    Ok(Object::String(
+        render_markdown(&state, &body, &options)?.html,
+    ))
+}
+
+/// marks the end of a post's excerpt in its markdown body
+const EXCERPT_SEPARATOR: &str = "<!-- more -->";
+
+/// reads a markdown file once and returns a map of its frontmatter (meta), the body
+/// without frontmatter (body), the rendered body (html), the rendered text before
+/// `<!-- more -->` (excerpt, or none without a marker) and the source path (path)
+pub(crate) fn load(state: Rc<RefCell<State>>, input: Option<Node<Object>>) -> AstryxResult<Object> {
+    let path = match input {
+        Some(input) => input.borrow().clone(),
+        None => state.borrow().require(Span::new_extra("path", BUILTIN))?,
+    };
+
+    let options = markdown_options(&state.borrow())?;
+    let (meta, body) = read_frontmatter(Rc::clone(&state), path.clone())?;
+
+    let excerpt = match body.find(EXCERPT_SEPARATOR) {
+        Some(end) => Object::String(render_markdown(&state, &body[..end], &options)?.html),
+        None => Object::None,
+    };
+
+    let mut map = HashMap::new();
+    map.insert(String::from("meta"), Node::new(meta));
+    map.insert(
+        String::from("html"),
+        Node::new(Object::String(
+            render_markdown(&state, &body, &options)?.html,
+        )),
+    );
+    map.insert(String::from("body"), Node::new(Object::String(body)));
+    map.insert(String::from("excerpt"), Node::new(excerpt));
+    map.insert(
+        String::from("path"),
+        Node::new(Object::Path(path.to_string())),
+    );
+
+    Ok(Object::Map(map))
+}
+
+/// renders markdown and returns a map of the html, a nested heading tree
+/// (text, level, slug, children), word count and reading time in minutes
+pub(crate) fn toc(state: Rc<RefCell<State>>, input: Option<Node<Object>>) -> AstryxResult<Object> {
+    let path = match input {
+        Some(input) => input.borrow().clone(),
+        None => state.borrow().require(Span::new_extra("path", BUILTIN))?,
+    };
+
+    let options = markdown_options(&state.borrow())?;
+    let (_, body) = read_frontmatter(Rc::clone(&state), path)?;
+    let document = render_markdown(&state, &body, &options)?;
+
+    let mut map = HashMap::new();
+    map.insert(
+        String::from("headings"),
+        Node::new(headings_to_object(markdown::heading_tree(
+            &document.headings,
+        ))),
+    );
+    map.insert(
+        String::from("words"),
+        Node::new(Object::Number(document.words as f64)),
+    );
+    map.insert(
+        String::from("readingtime"),
+        Node::new(Object::Number(document.reading_time() as f64)),
+    );
+    map.insert(
+        String::from("html"),
+        Node::new(Object::String(document.html)),
+    );
+
+    Ok(Object::Map(map))
+}
+
+/// renders markdown, evaluating shortcodes in the scope the builtin was called from
+fn render_markdown(
+    state: &Rc<RefCell<State>>,
+    source: &str,
+    options: &MarkdownOptions,
+) -> AstryxResult<markdown::Document> {
+    let scope = state.borrow().caller().unwrap_or_else(|| Rc::clone(state));
+
+    markdown::render_with(source, options, |call, body| {
+        shortcode(Rc::clone(&scope), call, body)
+    })
+}
+
+/// evaluates a shortcode function call, passing its rendered body (if any) as input
+fn shortcode(state: Rc<RefCell<State>>, call: &str, body: Option<String>) -> AstryxResult<String> {
+    let expr = match parser::parse(Span::new_extra(call, "shortcode")) {
+        Ok((rest, Statement::Expression(expr @ Expression::FunctionCall(_))))
+            if rest.trim().is_empty() =>
+        {
+            expr
+        }
+        _ => {
+            return Err(AstryxError::Generic(format!(
+                "shortcodes must be function calls: {{{{ {} }}}}",
+                call
+            )))
+        }
     };
 
-    let content = read(state, Some(Node::new(path)))?.to_string();
+    match crate::eval::eval_expression(state, &expr, body.map(|b| Node::new(Object::String(b))))? {
+        Object::None => Ok(String::new()),
+        object => Ok(object.to_string()),
+    }
+}
+
+/// wraps its input in an aside, mostly for use as a shortcode in markdown:
+/// `{{ callout(kind: "warn") }}...{{ end }}`
+pub(crate) fn callout(
+    state: Rc<RefCell<State>>,
+    input: Option<Node<Object>>,
+) -> AstryxResult<Object> {
+    let kind = state
+        .borrow()
+        .get("kind")
+        .map_or_else(|| String::from("note"), |kind| kind.to_string());
+    let body = input.map(|i| i.borrow().to_string()).unwrap_or_default();
 
-    Ok(Object::String(markdown::parse(&content).unwrap()))
+    Ok(Object::String(format!(
+        "<aside class=\"callout callout-{}\">{}</aside>",
+        markdown::slugify(&kind),
+        body
+    )))
+}
+
+fn headings_to_object(headings: Vec<HeadingNode>) -> Object {
+    Object::Array(
+        headings
+            .into_iter()
+            .map(|node| {
+                let mut map = HashMap::new();
+                map.insert(
+                    String::from("text"),
+                    Node::new(Object::String(node.heading.text)),
+                );
+                map.insert(
+                    String::from("level"),
+                    Node::new(Object::Number(node.heading.level as f64)),
+                );
+                map.insert(
+                    String::from("slug"),
+                    Node::new(Object::String(node.heading.slug)),
+                );
+                map.insert(
+                    String::from("children"),
+                    Node::new(headings_to_object(node.children)),
+                );
+                Node::new(Object::Map(map))
+            })
+            .collect(),
+    )
+}
+
+/// returns the stylesheet for `markdown(highlight: "classes")` output
+pub(crate) fn syntax_css(
+    state: Rc<RefCell<State>>,
+    _input: Option<Node<Object>>,
+) -> AstryxResult<Object> {
+    let options = highlighter_options(&state.borrow())?;
+
+    Ok(Object::String(markdown::theme_css(&options)?))
+}
+
+/// collect markdown options from function arguments
+fn markdown_options(state: &State) -> AstryxResult<MarkdownOptions> {
+    let defaults = MarkdownOptions::default();
+    let flag = |name: &str, default: bool| state.get(name).map_or(default, |o| is_truthy(&o));
+
+    Ok(MarkdownOptions {
+        highlight: highlighter_options(state)?,
+        tables: flag("tables", defaults.tables),
+        footnotes: flag("footnotes", defaults.footnotes),
+        strikethrough: flag("strikethrough", defaults.strikethrough),
+        tasklists: flag("tasklists", defaults.tasklists),
+        smart_punctuation: flag("smartpunctuation", defaults.smart_punctuation),
+        heading_ids: flag("headingids", defaults.heading_ids),
+        heading_anchors: flag("anchors", defaults.heading_anchors),
+    })
+}
+
+/// collect syntax highlighting options from function arguments
+fn highlighter_options(state: &State) -> AstryxResult<HighlighterOptions> {
+    let mut options = HighlighterOptions::default();
+
+    if let Some(theme) = state.get("theme") {
+        options.theme = theme.to_string();
+    }
+
+    if let Some(style) = state.get("highlight") {
+        options.style = match style.to_string().as_str() {
+            "inline" => HighlightStyle::Inline,
+            "classes" => HighlightStyle::Classes,
+            s => {
+                return Err(AstryxError::Generic(format!(
+                    "unknown highlight style: {} (expected inline or classes)",
+                    s
+                )))
+            }
+        };
+    }
+
+    if let Some(line_numbers) = state.get("linenumbers") {
+        options.line_numbers = is_truthy(&line_numbers);
+    }
+
+    options.syntax_dir = state.get("syntaxes").map(|dir| dir.to_string());
+    options.theme_dir = state.get("themes").map(|dir| dir.to_string());
+
+    Ok(options)
 }
 
 pub(crate) fn parse_frontmatter<'a>(
@@ -91,17 +322,29 @@ pub(crate) fn parse_frontmatter<'a>(
 ) -> AstryxResult<Object> {
     let path = match input {
         Some(input) => input.borrow().clone(),
-        None => state.borrow().require(Span::new_extra("path", "error"))?,
+        None => state.borrow().require(Span::new_extra("path", BUILTIN))?,
     };
 
-    let content = read(state, Some(Node::new(path)))?.to_string();
+    let (yaml, _body) = read_frontmatter(state, path)?;
+
+    Ok(yaml)
+}
+
+/// reads a file and splits it into its frontmatter, as a map, and the rest of the document
+fn read_frontmatter(state: Rc<RefCell<State>>, path: Object) -> AstryxResult<(Object, String)> {
+    let content = read(state, Some(Node::new(path.clone())))?.to_string();
 
-    let (yaml, _document) = frontmatter::parse(&content).unwrap();
+    let (yaml, body) = frontmatter::parse(&content).map_err(|e| {
+        AstryxError::Generic(format!(
+            "could not parse frontmatter in {}: {}",
+            path.to_string(),
+            e
+        ))
+    })?;
 
-    if let Some(yaml) = yaml {
-        Ok(yaml.into())
-    } else {
-        Ok(Object::Map(HashMap::new()))
+    match yaml {
+        Some(yaml) => Ok((yaml.into(), body)),
+        None => Ok((Object::Map(HashMap::new()), body)),
     }
 }
 
@@ -109,7 +352,7 @@ pub(crate) fn parse_frontmatter<'a>(
 //     state: Rc<RefCell<State>>,
 //     input: Option<Node<Object>>,
 // ) -> AstryxResult<Object> {
-//     let path = state.borrow().require(Span::new_extra("path", "error"))?;
+//     let path = state.borrow().require(Span::new_extra("path", BUILTIN))?;
 
 //     // Ok(input.unwrap().borrow().clone())
 
@@ -120,7 +363,7 @@ pub(crate) fn asset<'a>(
     state: Rc<RefCell<State>>,
     _input: Option<Node<Object>>,
 ) -> AstryxResult<Object> {
-    let path = state.borrow().require(Span::new_extra("path", "error"))?;
+    let path = state.borrow().require(Span::new_extra("path", BUILTIN))?;
 
     // Ok(input.unwrap().borrow().clone())
 
@@ -151,14 +394,42 @@ pub(crate) fn read<'a>(
 ) -> AstryxResult<Object> {
     let path = match input {
         Some(input) => input.borrow().clone(),
-        None => state.borrow().require(Span::new_extra("path", "error"))?,
+        None => state.borrow().require(Span::new_extra("path", BUILTIN))?,
     };
 
     match path {
-        Object::String(s) | Object::Path(s) => std::fs::read_to_string(s)
-            .map(Object::String)
-            .map_err(|_| AstryxError::Generic("<can't read file>".into())),
-        _ => unimplemented!(), // return error
+        Object::String(s) | Object::Path(s) => {
+            state.borrow().track_input(&s);
+            std::fs::read_to_string(&s)
+                .map(Object::String)
+                .map_err(|e| {
+                    AstryxError::with_loc(
+                        Span::new_extra("path", BUILTIN),
+                        AstryxErrorKind::ReadError {
+                            path: s,
+                            reason: e.to_string(),
+                        },
+                    )
+                })
+        }
+        found => Err(AstryxError::with_loc(
+            Span::new_extra("path", BUILTIN),
+            AstryxErrorKind::MismatchedType {
+                expected: String::from("a path"),
+                found: found.type_name().into(),
+            },
+        )),
+    }
+}
+
+/// interpret a function argument as an on/off switch
+fn is_truthy(obj: &Object) -> bool {
+    match obj {
+        Object::None => false,
+        Object::Boolean(b) => *b,
+        Object::Number(n) => *n != 0.0,
+        Object::String(s) => !matches!(s.as_str(), "" | "false" | "no" | "off"),
+        _ => true,
     }
 }
 
@@ -1,7 +1,7 @@
-use error::{AstryxError, AstryxErrorKind, AstryxResult};
+use error::{suggest, AstryxError, AstryxErrorKind, AstryxResult};
 use html::HTMLElement;
 use models::{object::Object, state::State};
-use parser::{Expression, Statement, StringToken};
+use parser::{Expression, Span, Statement, StringToken, TextBlock};
 use rctree::Node;
 use std::cell::RefCell;
 use std::{collections::HashMap, rc::Rc};
@@ -18,11 +18,14 @@ pub(crate) fn eval_statement<'a>(
             for (ident, expr) in e.attributes {
                 attributes.insert(
                     ident.fragment().to_string(),
-                    eval_expression(Rc::clone(&state), &expr, None)?.into(),
+                    eval_attribute(&state, ident, &expr)?,
                 );
             }
 
-            let element = HTMLElement::new(e.ident.fragment(), attributes).expect("valid html");
+            let ident = e.ident;
+            let element = HTMLElement::new(ident.fragment(), attributes).map_err(|_| {
+                AstryxError::with_loc(ident, AstryxErrorKind::UnexpectedToken(ident.to_string()))
+            })?;
 
             // println!("element: {:?}", element);
             // todo, these really should be html nodes, so that we can optimise them all later...
@@ -46,30 +49,50 @@ pub(crate) fn eval_statement<'a>(
                 )?)));
             }
 
+            if let Some(block) = e.block {
+                node.append(Node::new(Object::String(eval_text_block(
+                    Rc::clone(&state),
+                    block,
+                )?)));
+            }
+
             for child in statement.children() {
                 // println!("child");
-                let obj = eval_statement(&child, Rc::clone(&state))?;
-                // node.append child
-                node.append(obj);
+                if let Some(obj) = eval_child(&child, &state)? {
+                    // node.append child
+                    node.append(obj);
+                }
             }
 
             Ok(node)
         }
         Statement::Expression(expr) => {
-            let return_objects: Vec<Node<Object>> = statement
+            let return_objects: Vec<Node<Object>> = match statement
                 .children()
-                .map(|child| eval_statement(&child, Rc::clone(&state)))
-                .collect::<AstryxResult<Vec<Node<Object>>>>()?;
+                .map(|child| eval_child(&child, &state))
+                .collect::<AstryxResult<Option<Vec<Node<Object>>>>>()?
+            {
+                Some(objects) => objects,
+                // a child failed and was reported, so don't call the function with half its input
+                None => return Ok(Node::new(Object::None)),
+            };
 
             // for statement in statement.children() {
 
             // }
 
-            let return_value = eval_expression(
-                Rc::clone(&state),
-                &expr,
-                Some(Node::new(Object::Array(return_objects))),
-            )?;
+            // children are passed in as input, without any it's left to the arguments
+            let input = match return_objects.is_empty() {
+                true => None,
+                false => Some(Node::new(Object::Array(return_objects))),
+            };
+
+            let return_value = eval_expression(Rc::clone(&state), &expr, input)?;
+
+            // a value which ends up in the page has to be something that can be written
+            if let Some(found) = unrenderable(&return_value) {
+                return Err(mismatched_type(&expr, "text or html", found));
+            }
 
             // state
             //     .borrow()
@@ -87,35 +110,53 @@ pub(crate) fn eval_statement<'a>(
                 t,
             )?)));
         }
+        Statement::TextBlock(block) => Ok(Node::new(Object::String(eval_text_block(
+            Rc::clone(&state),
+            block,
+        )?))),
         Statement::Binding(ident, expr) => {
-            let obj = eval_expression(Rc::clone(&state), &expr, None)?;
-            state.borrow_mut().bind(ident.fragment(), obj.clone())?;
+            let obj = match eval_expression(Rc::clone(&state), &expr, None) {
+                Ok(obj) => obj,
+                // bind something anyway, or every use of the variable is an error too
+                Err(e) if state.borrow().recovering() => {
+                    state.borrow().report(e);
+                    Object::None
+                }
+                Err(e) => return Err(e),
+            };
+            state.borrow_mut().bind_at(ident, obj.clone())?;
             return Ok(Node::new(Object::None));
         }
-        Statement::Comment(_) => Ok(Node::new(Object::None)),
+        Statement::Comment(c) | Statement::BlockComment(c) | Statement::DocComment(c) => {
+            // comments outside of a route would go before the page
+            match state.borrow().emitting_comments() && statement.parent().is_some() {
+                true => Ok(Node::new(Object::String(html_comment(c.fragment())))),
+                false => Ok(Node::new(Object::None)),
+            }
+        }
         Statement::ForLoop { ident, expr } => {
             let iter: Object = eval_expression(Rc::clone(&state), &expr, None)?;
+            let mut node = Node::new(Object::None);
 
             if let Object::Array(array) = iter {
                 for index in array {
                     let childstate = state.clone();
                     childstate
                         .borrow_mut()
-                        .bind(&ident.to_string(), index.borrow().clone())?;
+                        .bind_at(ident, index.borrow().clone())?;
                     for child in statement.children() {
                         // BUG HERE - CHILDSTATE IS THE SAME
                         // println!("---{:?}", &childstate.borrow().local);
-                        let _ = eval_statement(&child, Rc::clone(&childstate))?;
+                        if let Some(obj) = eval_child(&child, &childstate)? {
+                            node.append(obj);
+                        }
                     }
                 }
             } else {
-                return Err(AstryxError::LocatedError(
-                    ident.into(),
-                    AstryxErrorKind::Unexpected,
-                ));
+                return Err(AstryxError::with_loc(ident, AstryxErrorKind::Unexpected));
             }
 
-            Ok(Node::new(Object::None)) // FIXME
+            Ok(node)
         }
         Statement::Route(route) => {
             // collect attributes
@@ -125,26 +166,40 @@ pub(crate) fn eval_statement<'a>(
             for (ident, expr) in route.attributes {
                 attributes.insert(
                     ident.fragment().to_string(),
-                    eval_expression(Rc::clone(&state), &expr, None)?.into(),
+                    eval_attribute(&state, ident, &expr)?,
                 );
             }
 
             match route.ident.to_string().as_str() {
                 "route" => {
-                    let path = attributes.get("path").ok_or(AstryxError::LocatedError(
-                        route.ident.into(),
-                        AstryxErrorKind::MissingRequiredArgument(route.ident.to_string()),
-                    ))?;
+                    let ident = route.ident;
+                    let path = attributes.get("path").ok_or_else(|| {
+                        AstryxError::with_loc(
+                            ident,
+                            AstryxErrorKind::MissingRequiredArgument(String::from("path")),
+                        )
+                    })?;
 
                     let mut node = Node::new(Object::HTMLPage(path.clone()));
+                    let dependencies = state.borrow().dependencies();
+
+                    // an empty page is left as it is on disk, or rendered by another shard
+                    if dependencies.borrow_mut().skip_route(path, ident.into())? {
+                        return Ok(node);
+                    }
+
+                    dependencies.borrow_mut().start_route(path);
 
                     for child in statement.children() {
                         // println!("child");
-                        let obj = eval_statement(&child, Rc::clone(&state))?;
-                        // node.append child
-                        node.append(obj);
+                        if let Some(obj) = eval_child(&child, &state)? {
+                            // node.append child
+                            node.append(obj);
+                        }
                     }
 
+                    dependencies.borrow_mut().end_route();
+
                     Ok(node)
                 }
                 _ => Ok(Node::new(Object::None)),
@@ -153,6 +208,21 @@ pub(crate) fn eval_statement<'a>(
     }
 }
 
+/// evaluate a child statement, or when recovering, report its error and carry on without it
+pub(crate) fn eval_child<'a>(
+    statement: &Node<Statement<'a>>,
+    state: &Rc<RefCell<State>>,
+) -> AstryxResult<Option<Node<Object>>> {
+    match eval_statement(statement, Rc::clone(state)) {
+        Ok(node) => Ok(Some(node)),
+        Err(e) if state.borrow().recovering() => {
+            state.borrow().report(e);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 pub fn eval_expression<'a>(
     state: Rc<RefCell<State>>,
     expr: &Expression<'a>,
@@ -160,7 +230,7 @@ pub fn eval_expression<'a>(
 ) -> AstryxResult<Object> {
     match expr {
         Expression::FunctionCall(ref f) => {
-            let mut inner = State::new();
+            let mut inner = State::call(Rc::clone(&state));
             // inner.program = Rc::clone(&state.borrow().program);
 
             // add function arguments into scope
@@ -168,30 +238,50 @@ pub fn eval_expression<'a>(
                 inner.bind(
                     &k.to_string(),
                     // evaluate the expression part of the argument
-                    eval_expression(Rc::clone(&state), expr, None).map_err(|_| {
-                        AstryxError::with_loc(*k, AstryxErrorKind::UnknownValue(k.to_string()))
-                    })?,
+                    eval_expression(Rc::clone(&state), expr, None)?,
                 )?;
             }
 
-            match eval_expression(state, &*f.ident, None)? {
-                Object::BuiltinFunction(builtin) => builtin(Rc::new(RefCell::new(inner)), input),
-                _ => unimplemented!(),
+            match eval_expression(Rc::clone(&state), &f.ident, None)? {
+                Object::BuiltinFunction(builtin) => {
+                    let result = builtin(Rc::new(RefCell::new(inner)), input);
+                    match span(&f.ident) {
+                        Some(call) => result.map_err(|e| e.at_call(call)),
+                        None => result,
+                    }
+                }
+                found => Err(not_a_function(&state.borrow(), &f.ident, &found)),
             }
         }
         Expression::Reference(r) => match input {
             Some(n) => match n.borrow().clone() {
                 Object::None => Ok(Object::None),
-                Object::Map(m) => Ok(m.get(&r.to_string()).unwrap().borrow().clone()),
-                _ => unimplemented!(),
+                Object::Map(m) => match m.get(*r.fragment()) {
+                    Some(value) => Ok(value.borrow().clone()),
+                    None => Err(unknown_key(*r, m.keys())),
+                },
+                found => Err(AstryxError::with_loc(
+                    *r,
+                    AstryxErrorKind::MismatchedType {
+                        expected: String::from("a map"),
+                        found: found.type_name().into(),
+                    },
+                )),
             },
-            None => state.borrow().require(*r),
+            None => state.borrow().resolve(*r),
         },
         Expression::Literal(l) => match l {
             parser::Literal::String(s) => Ok(Object::String(s.to_string())),
             parser::Literal::Number(_s, f) => Ok(Object::Number(f.clone())),
+            parser::Literal::Boolean(_s, b) => Ok(Object::Boolean(*b)),
         },
-        Expression::RelativePath(s) => Ok(Object::Path(s.to_string())),
+        // `./a.md` is `a.md`, but `../a.md` keeps its prefix
+        Expression::RelativePath(s) => Ok(Object::Path(
+            s.fragment()
+                .strip_prefix("./")
+                .unwrap_or(s.fragment())
+                .to_string(),
+        )),
         Expression::Array(arr) => Ok(Object::Array(
             arr.iter()
                 .map(|el| eval_expression(Rc::clone(&state), el, None))
@@ -200,7 +290,7 @@ pub fn eval_expression<'a>(
                 .map(Node::new)
                 .collect(),
         )),
-        Expression::GlobPattern(s) => crate::util::glob_files(s),
+        Expression::GlobPattern(s) => crate::util::glob_files(s, &state.borrow()),
         Expression::Index(l, r) => {
             let lexpr: Object = eval_expression(Rc::clone(&state), l, None)?;
 
@@ -342,3 +432,101 @@ fn eval_interpolation<'a>(
         .into_iter()
         .collect())
 }
+
+/// a comment as `<!-- -->`, with `<` and `>` escaped so it can't end early
+fn html_comment(comment: &str) -> String {
+    format!(
+        "<!-- {} -->",
+        comment.trim().replace('<', "&lt;").replace('>', "&gt;")
+    )
+}
+
+/// the lines of a text block, interpolated (unless it's raw) and joined with newlines
+fn eval_text_block<'a>(state: Rc<RefCell<State>>, block: TextBlock<'a>) -> AstryxResult<String> {
+    Ok(block
+        .lines
+        .into_iter()
+        .map(|line| eval_interpolation(Rc::clone(&state), line))
+        .collect::<AstryxResult<Vec<String>>>()?
+        .join("\n"))
+}
+
+/// calling something that isn't a function, pointing out where it was defined
+fn not_a_function(state: &State, ident: &Expression, found: &Object) -> AstryxError {
+    match ident {
+        Expression::Reference(r) => {
+            let error =
+                AstryxError::with_loc(*r, AstryxErrorKind::NotAFunction(r.fragment().to_string()));
+            match state.location(r.fragment()) {
+                Some(location) => error.with_label(location, "defined here"),
+                None => error,
+            }
+        }
+        _ => mismatched_type(ident, "a function", found.type_name()),
+    }
+}
+
+/// evaluate the value of an element or route attribute, which has to be text
+fn eval_attribute<'a>(
+    state: &Rc<RefCell<State>>,
+    ident: Span<'a>,
+    expr: &Expression<'a>,
+) -> AstryxResult<String> {
+    let value = eval_expression(Rc::clone(state), expr, None)?;
+
+    value.as_text().ok_or_else(|| {
+        AstryxError::with_loc(
+            ident,
+            AstryxErrorKind::MismatchedType {
+                expected: String::from("text"),
+                found: value.type_name().into(),
+            },
+        )
+    })
+}
+
+/// the type of anything in a value which can't be written into a page
+fn unrenderable(value: &Object) -> Option<&'static str> {
+    match value {
+        Object::Map(_) | Object::BuiltinFunction(_) => Some(value.type_name()),
+        Object::Array(array) => array.iter().find_map(|node| unrenderable(&node.borrow())),
+        _ => None,
+    }
+}
+
+fn mismatched_type(expr: &Expression, expected: &str, found: &str) -> AstryxError {
+    let kind = AstryxErrorKind::MismatchedType {
+        expected: expected.into(),
+        found: found.into(),
+    };
+
+    match span(expr) {
+        Some(span) => AstryxError::with_loc(span, kind),
+        None => AstryxError::Generic(kind.message()),
+    }
+}
+
+/// where an expression starts in the source, if it has any source (`[]` doesn't)
+fn span<'a>(expr: &Expression<'a>) -> Option<Span<'a>> {
+    match expr {
+        Expression::FunctionCall(f) => span(&f.ident),
+        Expression::GlobPattern(s) | Expression::RelativePath(s) | Expression::Reference(s) => {
+            Some(*s)
+        }
+        Expression::Literal(parser::Literal::String(s))
+        | Expression::Literal(parser::Literal::Number(s, _))
+        | Expression::Literal(parser::Literal::Boolean(s, _)) => Some(*s),
+        Expression::Array(array) => array.first().and_then(span),
+        Expression::Index(l, _) => span(l),
+    }
+}
+
+/// a missing key in a map, suggesting one of `keys` if it looks like a typo
+fn unknown_key<'a, I: Iterator<Item = &'a String>>(key: Span, keys: I) -> AstryxError {
+    let error = AstryxError::with_loc(key, AstryxErrorKind::UnknownKey(key.fragment().to_string()));
+
+    match suggest(key.fragment(), keys.map(String::as_str)) {
+        Some(similar) => error.with_help(format!("did you mean `{}`?", similar)),
+        None => error,
+    }
+}
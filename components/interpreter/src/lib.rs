@@ -11,7 +11,7 @@ use parser::Statement;
 use rctree::Node;
 
 mod eval;
-use error::AstryxResult;
+use error::{AstryxError, AstryxResult};
 use models::{object::Object, state::State};
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -31,6 +31,20 @@ pub fn run<'a>(
         .collect::<AstryxResult<Vec<Node<Object>>>>()
 }
 
+/// run the interpreter without stopping at errors, returning every one. statements which
+/// fail are skipped, and variables they would have bound are left empty.
+pub fn check<'a>(nodes: &Vec<Node<Statement<'a>>>, state: Rc<RefCell<State>>) -> Vec<AstryxError> {
+    state.borrow_mut().recover();
+    let inner = &builtins::import(state);
+
+    for node in nodes {
+        let _ = eval::eval_child(node, inner);
+    }
+
+    let errors = inner.borrow().take_errors();
+    errors
+}
+
 /// evaluate a single expression with a given state
 pub fn eval<'a>(statement: Statement<'a>, state: Rc<RefCell<State>>) -> AstryxResult<Node<Object>> {
     eval::eval_statement(&Node::new(statement), Rc::clone(&state))
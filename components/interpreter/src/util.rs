@@ -1,10 +1,11 @@
 use error::{AstryxError, AstryxErrorKind, AstryxResult};
 use glob::Paths;
-use models::object::Object;
+use models::{object::Object, state::State};
 use parser::Span;
 use rctree::Node;
+use std::path::{Path, PathBuf};
 
-pub(crate) fn glob_files<'a>(s: &Span<'a>) -> AstryxResult<Object> {
+pub(crate) fn glob_files<'a>(s: &Span<'a>, state: &State) -> AstryxResult<Object> {
     let options = glob::MatchOptions {
         case_sensitive: false,
         require_literal_separator: false,
@@ -13,12 +14,17 @@ pub(crate) fn glob_files<'a>(s: &Span<'a>) -> AstryxResult<Object> {
 
     let mut files = Vec::new();
     let globs: Paths = glob::glob_with(&s.to_string(), options)
-        .map_err(|e| AstryxError::with_loc(*s, AstryxErrorKind::Unexpected))?;
+        .map_err(|e| AstryxError::with_loc(*s, AstryxErrorKind::InvalidGlob(e.msg.to_string())))?;
+
+    // only the listing matters here, reading a matched file tracks its contents.
+    // files added to or removed from any directory the pattern reaches change the result.
+    for dir in glob_dirs(&s.to_string()) {
+        state.track_input(dir);
+    }
 
     for file in globs {
-        // TODO wrap unwrap in error
-        let path = file.expect("file to unwrap");
-        let filepath: String = path.as_os_str().to_str().unwrap().into();
+        let path = file.map_err(|e| read_error(s, e.path(), e.error()))?;
+        let filepath: String = path.to_string_lossy().into();
 
         files.push(Node::new(Object::Path(filepath)));
     }
@@ -26,6 +32,54 @@ pub(crate) fn glob_files<'a>(s: &Span<'a>) -> AstryxResult<Object> {
     Ok(Object::Array(files))
 }
 
+/// the directory a glob pattern starts matching from, eg. `./posts` for `./posts/**/*.md`
+fn glob_base(pattern: &str) -> String {
+    let literal: Vec<&str> = pattern
+        .split('/')
+        .take_while(|part| !part.contains(['*', '?', '[']))
+        .collect();
+
+    match literal.join("/").as_str() {
+        "" => String::from("."),
+        base => base.into(),
+    }
+}
+
+/// every directory a glob pattern can match files in: its base, and the directories below
+/// it as deep as the pattern goes (all of them for `**`)
+fn glob_dirs(pattern: &str) -> Vec<PathBuf> {
+    let base = glob_base(pattern);
+    let parts: Vec<&str> = pattern
+        .split('/')
+        .skip_while(|part| !part.contains(['*', '?', '[']))
+        .collect();
+
+    let depth = if parts.contains(&"**") {
+        None
+    } else {
+        Some(parts.len().saturating_sub(1))
+    };
+
+    let mut dirs = vec![PathBuf::from(&base)];
+    subdirectories(Path::new(&base), depth, &mut dirs);
+    dirs
+}
+
+/// directories below `dir`, to `depth` levels or all of them. symlinks aren't followed, so
+/// a link back up the tree can't loop.
+fn subdirectories(dir: &Path, depth: Option<usize>, dirs: &mut Vec<PathBuf>) {
+    if depth == Some(0) {
+        return;
+    }
+
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            dirs.push(entry.path());
+            subdirectories(&entry.path(), depth.map(|d| d - 1), dirs);
+        }
+    }
+}
+
 pub(crate) fn import_files<'a>(s: &Span<'a>) -> AstryxResult<Object> {
     let options = glob::MatchOptions {
         case_sensitive: false,
@@ -35,13 +89,11 @@ pub(crate) fn import_files<'a>(s: &Span<'a>) -> AstryxResult<Object> {
 
     let mut files = Vec::new();
     let globs: Paths = glob::glob_with(&s.to_string(), options)
-        .map_err(|e| AstryxError::with_loc(*s, AstryxErrorKind::Unexpected))?;
+        .map_err(|e| AstryxError::with_loc(*s, AstryxErrorKind::InvalidGlob(e.msg.to_string())))?;
 
     for file in globs {
-        // TODO wrap unwrap in error
-        let path = file.expect("file to unwrap");
-        let filepath: String = path.as_os_str().to_str().unwrap().into();
-        let file_content = std::fs::read_to_string(filepath).unwrap();
+        let path = file.map_err(|e| read_error(s, e.path(), e.error()))?;
+        let file_content = std::fs::read_to_string(&path).map_err(|e| read_error(s, &path, &e))?;
 
         files.push(Node::new(Object::String(file_content)));
     }
@@ -52,5 +104,55 @@ pub(crate) fn import_files<'a>(s: &Span<'a>) -> AstryxResult<Object> {
 pub(crate) fn import_file<'a>(s: &Span<'a>) -> AstryxResult<Object> {
     std::fs::read_to_string(s.fragment().to_string())
         .map(Object::String)
-        .map_err(|e| AstryxError::with_loc(*s, AstryxErrorKind::Unexpected))
+        .map_err(|e| read_error(s, Path::new(s.fragment()), &e))
+}
+
+fn read_error(s: &Span, path: &Path, e: &std::io::Error) -> AstryxError {
+    AstryxError::with_loc(
+        *s,
+        AstryxErrorKind::ReadError {
+            path: path.to_string_lossy().into(),
+            reason: e.to_string(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("./posts/*.md"), "./posts");
+        assert_eq!(glob_base("./posts/**/*.md"), "./posts");
+        assert_eq!(glob_base("*.md"), ".");
+    }
+
+    #[test]
+    fn test_glob_dirs() {
+        let root = std::env::temp_dir().join(format!("astryx-glob-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("posts/a/b")).unwrap();
+        let dirs = |pattern: &str| {
+            let mut dirs = glob_dirs(&format!("{}/{}", root.display(), pattern));
+            dirs.sort();
+            dirs
+        };
+
+        assert_eq!(dirs("posts/*.md"), vec![root.join("posts")]);
+        assert_eq!(
+            dirs("posts/*/*.md"),
+            vec![root.join("posts"), root.join("posts/a")]
+        );
+        // empty directories too, as a file added to one later is a new match
+        assert_eq!(
+            dirs("posts/**/*.md"),
+            vec![
+                root.join("posts"),
+                root.join("posts/a"),
+                root.join("posts/a/b")
+            ]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
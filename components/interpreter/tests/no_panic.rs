@@ -0,0 +1,179 @@
+// property test: no source, however broken, can make the parser, interpreter or renderer
+// panic. programs are built from random pieces of astryx syntax and from random edits to a
+// valid program, with a fixed seed so failures can be reproduced. set ASTRYX_FUZZ_CASES to
+// run more of them.
+
+use error::display::{display_error, json_error};
+use error::AstryxError;
+use models::{Site, State};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::cell::RefCell;
+use std::panic;
+use std::rc::Rc;
+
+const DEFAULT_CASES: usize = 2000;
+
+const PIECES: &[&str] = &[
+    "let ",
+    "x",
+    "title",
+    "post",
+    " = ",
+    "=",
+    "\"hello\"",
+    "\"",
+    "${",
+    "}",
+    "${x}",
+    "%p",
+    "%div",
+    " ",
+    "@route",
+    "@",
+    " path=",
+    "\"/\"",
+    "for ",
+    " in ",
+    "[",
+    "]",
+    "[1, 2]",
+    "(",
+    ")",
+    "()",
+    ".",
+    ",",
+    ":",
+    "markdown()",
+    "load()",
+    "toc()",
+    "log(\"a\")",
+    "asset()",
+    "callout()",
+    "syntaxcss()",
+    "frontmatter()",
+    "./",
+    "./missing.md",
+    "./*.md",
+    "*",
+    "| ",
+    "|",
+    "|||",
+    " :raw",
+    "%script",
+    "-- ",
+    "--- ",
+    "--[",
+    "]--",
+    "123",
+    "1.5",
+    "true",
+    "false",
+    "x.y",
+    "x.y.z",
+    "\t",
+    "\\",
+    "é",
+    "🦀",
+    "\r",
+];
+
+const INDENTS: &[&str] = &["", "  ", "    ", "\t", " "];
+
+const SEED_PROGRAM: &str = "let title = \"hello\"
+let items = [1, 2]
+@route path=\"/\"
+  %div class=\"a\"
+    %p ${title}
+    for item in items
+      %p ${item}
+  | some ${title} text
+  -- a comment
+";
+
+/// a program of random lines of astryx syntax
+fn random_program(rng: &mut StdRng) -> String {
+    (0..rng.gen_range(1, 8))
+        .map(|_| {
+            let indent = INDENTS.choose(rng).unwrap();
+            let line: String = (0..rng.gen_range(0, 6))
+                .map(|_| *PIECES.choose(rng).unwrap())
+                .collect();
+            format!("{}{}\n", indent, line)
+        })
+        .collect()
+}
+
+/// a valid program with a few characters inserted, removed or replaced
+fn mutated_program(rng: &mut StdRng) -> String {
+    let mut chars: Vec<char> = SEED_PROGRAM.chars().collect();
+
+    for _ in 0..rng.gen_range(1, 6) {
+        let at = rng.gen_range(0, chars.len());
+        let piece: char = PIECES.choose(rng).unwrap().chars().next().unwrap_or(' ');
+
+        match rng.gen_range(0, 3) {
+            0 => chars.insert(at, piece),
+            1 => {
+                chars.remove(at);
+            }
+            _ => chars[at] = piece,
+        }
+    }
+
+    chars.into_iter().collect()
+}
+
+/// everything the cli does with a source file, short of writing it to disk
+fn run(source: &str) {
+    let show = |e: &AstryxError| {
+        let _ = display_error(e, "fuzz.astryx");
+        let _ = json_error(e, "fuzz.astryx");
+    };
+
+    if let Err(e) = parser::run(source, "fuzz.astryx") {
+        show(&AstryxError::from(e));
+    }
+
+    let (nodes, errors) = parser::run_all(source, "fuzz.astryx");
+    for e in errors {
+        show(&AstryxError::from(e));
+    }
+
+    for node in &nodes {
+        let _ = node.borrow().inspect();
+    }
+
+    match interpreter::run(&nodes, Rc::new(RefCell::new(State::new()))) {
+        Ok(objects) => {
+            for object in &objects {
+                let _ = object.borrow().inspect();
+            }
+            let _ = Site::render(objects);
+        }
+        Err(e) => show(&e),
+    }
+
+    for e in interpreter::check(&nodes, Rc::new(RefCell::new(State::new()))) {
+        show(&e);
+    }
+}
+
+#[test]
+fn test_no_panics() {
+    let cases = std::env::var("ASTRYX_FUZZ_CASES")
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(DEFAULT_CASES);
+
+    for case in 0..cases {
+        let mut rng = StdRng::seed_from_u64(case as u64);
+        let source = match case % 2 {
+            0 => random_program(&mut rng),
+            _ => mutated_program(&mut rng),
+        };
+
+        if panic::catch_unwind(|| run(&source)).is_err() {
+            panic!("case {} panicked on input:\n{:?}", case, source);
+        }
+    }
+}
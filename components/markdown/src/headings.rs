@@ -0,0 +1,202 @@
+// heading ids, anchor links and the table of contents
+
+use pulldown_cmark::{CowStr, Event, Tag};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub level: u32,
+    pub text: String,
+    pub slug: String,
+}
+
+/// a heading and the lower level headings that follow it
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadingNode {
+    pub heading: Heading,
+    pub children: Vec<HeadingNode>,
+}
+
+/// nest a flat list of headings by level, eg. h3s under the preceding h2.
+/// skipped levels (h1 then h3) nest directly.
+pub fn heading_tree(headings: &[Heading]) -> Vec<HeadingNode> {
+    let mut roots: Vec<HeadingNode> = Vec::new();
+    let mut stack: Vec<HeadingNode> = Vec::new();
+
+    for heading in headings {
+        while stack
+            .last()
+            .is_some_and(|node| node.heading.level >= heading.level)
+        {
+            close(&mut stack, &mut roots);
+        }
+
+        stack.push(HeadingNode {
+            heading: heading.clone(),
+            children: Vec::new(),
+        });
+    }
+
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots);
+    }
+
+    roots
+}
+
+fn close(stack: &mut Vec<HeadingNode>, roots: &mut Vec<HeadingNode>) {
+    if let Some(node) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+}
+
+/// buffers the events of each heading so an `id` can be derived from its text
+pub(crate) struct HeadingIds<'a> {
+    ids: bool,
+    anchors: bool,
+    slugs: HashMap<String, usize>,
+    current: Option<(u32, Vec<Event<'a>>)>,
+    pub(crate) headings: Vec<Heading>,
+}
+
+impl<'a> HeadingIds<'a> {
+    pub(crate) fn new(ids: bool, anchors: bool) -> Self {
+        HeadingIds {
+            ids,
+            anchors,
+            slugs: HashMap::new(),
+            current: None,
+            headings: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        match event {
+            Event::Start(Tag::Heading(level)) => {
+                self.current = Some((level, Vec::new()));
+                Vec::new()
+            }
+            Event::End(Tag::Heading(level)) => {
+                let (_, events) = self.current.take().unwrap_or((level, Vec::new()));
+                self.finish(level, events)
+            }
+            event => match self.current.as_mut() {
+                Some((_, events)) => {
+                    events.push(event);
+                    Vec::new()
+                }
+                None => vec![event],
+            },
+        }
+    }
+
+    fn finish(&mut self, level: u32, mut events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let text: String = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                _ => None,
+            })
+            .collect();
+        let slug = self.unique_slug(&text);
+
+        self.headings.push(Heading {
+            level,
+            text,
+            slug: slug.clone(),
+        });
+
+        if !self.ids {
+            events.insert(0, Event::Start(Tag::Heading(level)));
+            events.push(Event::End(Tag::Heading(level)));
+            return events;
+        }
+
+        // slugs are alphanumerics and dashes only, so they need no escaping
+        let mut open = format!("<h{} id=\"{}\">", level, slug);
+        if self.anchors {
+            open.push_str(&format!(
+                "<a class=\"anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+                slug
+            ));
+        }
+
+        events.insert(0, Event::Html(CowStr::from(open)));
+        events.push(Event::Html(CowStr::from(format!("</h{}>\n", level))));
+        events
+    }
+
+    /// repeated headings get -1, -2... suffixes, like github
+    fn unique_slug(&mut self, text: &str) -> String {
+        let slug = slugify(text);
+        let count = self.slugs.entry(slug.clone()).or_insert(0);
+        let unique = match *count {
+            0 => slug,
+            n => format!("{}-{}", slug, n),
+        };
+        *count += 1;
+        unique
+    }
+}
+
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(
+            slugify("  multiple   spaces -- here "),
+            "multiple-spaces-here"
+        );
+        assert_eq!(slugify("`code` and Ünïcode"), "code-and-ünïcode");
+    }
+
+    #[test]
+    fn test_heading_tree() {
+        let heading = |level, text: &str| Heading {
+            level,
+            text: text.into(),
+            slug: slugify(text),
+        };
+
+        let tree = heading_tree(&[
+            heading(1, "a"),
+            heading(2, "b"),
+            heading(3, "c"),
+            heading(2, "d"),
+            heading(1, "e"),
+        ]);
+
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].children.len(), 2);
+        assert_eq!(tree[0].children[0].children[0].heading.text, "c");
+        assert_eq!(tree[1].heading.text, "e");
+    }
+
+    #[test]
+    fn test_unique_slugs() {
+        let mut ids = HeadingIds::new(true, false);
+        assert_eq!(ids.unique_slug("Intro"), "intro");
+        assert_eq!(ids.unique_slug("Intro"), "intro-1");
+        assert_eq!(ids.unique_slug("intro"), "intro-2");
+    }
+}
@@ -1,59 +1,393 @@
 // syntax highlighting for source text
 
+use error::{AstryxError, AstryxResult};
+use pulldown_cmark::escape::escape_html;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
+use syntect::highlighting::{Color, Theme, ThemeSet};
 use syntect::html::{
-    start_highlighted_html_snippet, styled_line_to_highlighted_html,
-    IncludeBackground,
+    css_for_theme_with_class_style, start_highlighted_html_snippet,
+    styled_line_to_highlighted_html, ClassStyle, IncludeBackground,
 };
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{
+    BasicScopeStackOp, ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet,
+};
+use syntect::util::LinesWithEndings;
+
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// prefix for generated classes so they don't collide with site styles
+const CLASS_PREFIX: &str = "hl-";
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed {
+    prefix: CLASS_PREFIX,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HighlightStyle {
+    /// inline `style=` attributes from the theme
+    Inline,
+    /// `class=` spans, styled by the sheet from `theme_css`
+    Classes,
+}
+
+#[derive(Debug, Clone)]
+pub struct HighlighterOptions {
+    pub theme: String,
+    pub style: HighlightStyle,
+    /// number every line of every code block
+    pub line_numbers: bool,
+    /// folder of extra `.sublime-syntax` files
+    pub syntax_dir: Option<String>,
+    /// folder of extra `.tmTheme` files
+    pub theme_dir: Option<String>,
+}
+
+impl Default for HighlighterOptions {
+    fn default() -> Self {
+        HighlighterOptions {
+            theme: DEFAULT_THEME.into(),
+            style: HighlightStyle::Inline,
+            line_numbers: false,
+            syntax_dir: None,
+            theme_dir: None,
+        }
+    }
+}
+
+/// a fenced code block info string, eg. ```` ```rust {3-5,8} ````
+#[derive(Debug, Default, PartialEq)]
+struct CodeBlockInfo {
+    language: Option<String>,
+    /// inclusive, 1-indexed line ranges
+    highlighted: Vec<(usize, usize)>,
+    line_numbers: bool,
+}
+
+impl CodeBlockInfo {
+    fn parse(info: &str) -> Self {
+        let (language, attributes) = match info.find('{') {
+            Some(start) => (&info[..start], info[start + 1..].trim_end_matches('}')),
+            None => (info, ""),
+        };
+
+        let mut block = CodeBlockInfo {
+            language: language.split_whitespace().next().map(String::from),
+            ..Default::default()
+        };
+
+        for attribute in attributes.split(',').map(str::trim) {
+            if attribute == "linenos" {
+                block.line_numbers = true;
+                continue;
+            }
+
+            let mut range = attribute.splitn(2, '-').map(|n| n.trim().parse::<usize>());
+            match (range.next(), range.next()) {
+                (Some(Ok(start)), None) => block.highlighted.push((start, start)),
+                (Some(Ok(start)), Some(Ok(end))) => block.highlighted.push((start, end)),
+                _ => (), // ignore anything we don't understand, like other generators do
+            }
+        }
+
+        block
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.highlighted
+            .iter()
+            .any(|(start, end)| line >= *start && line <= *end)
+    }
+}
 
+/// collects the text of one code block at a time, so the whole block is highlighted
+/// with a single parser state and multi-line constructs are coloured correctly.
 pub struct SyntaxHighlighter {
-    syntax: String, // todo: enum
     syntaxes: SyntaxSet,
-    themes: ThemeSet,
-    pub is_highlighting: bool,
+    theme: Theme,
+    style: HighlightStyle,
+    line_numbers: bool,
+    block: Option<(CodeBlockInfo, String)>,
 }
 
 impl SyntaxHighlighter {
-    pub fn new() -> Self {
-        SyntaxHighlighter {
-            themes: ThemeSet::load_defaults(),
-            syntaxes: SyntaxSet::load_defaults_newlines(),
-            syntax: String::from("rs"),
-            is_highlighting: false,
-        }
+    pub fn new(options: &HighlighterOptions) -> AstryxResult<Self> {
+        Ok(SyntaxHighlighter {
+            theme: load_theme(options)?,
+            syntaxes: load_syntaxes(options)?,
+            style: options.style,
+            line_numbers: options.line_numbers,
+            block: None,
+        })
     }
 
-    pub fn set_syntax_by_file_extension(&mut self, ext: &str) {
-        self.syntax = ext.into();
+    pub fn is_highlighting(&self) -> bool {
+        self.block.is_some()
     }
 
-    pub fn start_highlight(&mut self) -> String {
-        self.is_highlighting = true;
-        let (html, _) = start_highlighted_html_snippet(
-            &self.themes.themes["base16-ocean.dark"],
-        );
+    /// takes a fenced code block info string, or "" for indented blocks
+    pub fn start_block(&mut self, info: &str) {
+        self.block = Some((CodeBlockInfo::parse(info), String::new()));
+    }
+
+    pub fn push_text(&mut self, text: &str) {
+        if let Some((_, source)) = self.block.as_mut() {
+            source.push_str(text);
+        }
+    }
+
+    /// highlight the collected block and return it as html
+    pub fn end_block(&mut self) -> String {
+        let (info, source) = match self.block.take() {
+            Some(block) => block,
+            None => return String::new(),
+        };
+
+        let lines = match self.style {
+            HighlightStyle::Inline => self.inline_lines(&info, &source),
+            HighlightStyle::Classes => self.classed_lines(&info, &source),
+        };
+
+        let line_numbers = self.line_numbers || info.line_numbers;
+        let mut html = String::new();
+
+        if let Some(language) = &info.language {
+            html.push_str(&format!(
+                "<div class=\"code-block\" data-lang=\"{lang}\"><div class=\"code-lang\">{lang}</div>",
+                lang = escaped(language)
+            ));
+        }
+
+        html.push_str(&match self.style {
+            HighlightStyle::Inline => start_highlighted_html_snippet(&self.theme).0,
+            HighlightStyle::Classes => String::from("<pre class=\"hl-code\">"),
+        });
+
+        for (index, line) in lines.iter().enumerate() {
+            let number = index + 1;
+            let highlighted = info.is_highlighted(number);
+
+            html.push_str("<span class=\"code-line");
+            if highlighted {
+                html.push_str(" code-line-highlight");
+            }
+            html.push('"');
+            if let (true, HighlightStyle::Inline, Some(color)) =
+                (highlighted, self.style, self.theme.settings.line_highlight)
+            {
+                html.push_str(&format!(
+                    " style=\"display:flex;background-color:{};\"",
+                    css_color(color)
+                ));
+            }
+            html.push('>');
+
+            if line_numbers {
+                html.push_str(&self.line_number(number, lines.len()));
+            }
+
+            html.push_str(line);
+            html.push_str("</span>");
+        }
+
+        html.push_str("</pre>");
+
+        if info.language.is_some() {
+            html.push_str("</div>");
+        }
+
         html
     }
 
-    pub fn stop_highlight(&mut self) -> String {
-        self.is_highlighting = false;
-        String::from("</pre>")
+    fn line_number(&self, number: usize, count: usize) -> String {
+        let width = count.to_string().len();
+        let style = match (self.style, self.theme.settings.gutter_foreground) {
+            (HighlightStyle::Inline, Some(color)) => format!(
+                " style=\"user-select:none;margin-right:1em;color:{};\"",
+                css_color(color)
+            ),
+            (HighlightStyle::Inline, None) => {
+                String::from(" style=\"user-select:none;margin-right:1em;opacity:0.5;\"")
+            }
+            (HighlightStyle::Classes, _) => String::new(),
+        };
+
+        format!(
+            "<span class=\"code-line-number\"{}>{:>width$}</span>",
+            style,
+            number,
+            width = width
+        )
+    }
+
+    /// unknown or missing languages fall back to plain text
+    fn syntax(&self, info: &CodeBlockInfo) -> &SyntaxReference {
+        info.language
+            .as_ref()
+            .and_then(|token| self.syntaxes.find_syntax_by_token(token))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text())
+    }
+
+    fn inline_lines(&self, info: &CodeBlockInfo, source: &str) -> Vec<String> {
+        let mut h = HighlightLines::new(self.syntax(info), &self.theme);
+
+        LinesWithEndings::from(source)
+            .map(|line| {
+                let regions = h.highlight(line, &self.syntaxes);
+                styled_line_to_highlighted_html(&regions[..], IncludeBackground::No)
+            })
+            .collect()
+    }
+
+    /// every line is closed off and reopened with the scopes still on the stack,
+    /// so each line can be wrapped in its own element.
+    fn classed_lines(&self, info: &CodeBlockInfo, source: &str) -> Vec<String> {
+        let mut parser = ParseState::new(self.syntax(info));
+        let mut stack = ScopeStack::new();
+
+        LinesWithEndings::from(source)
+            .map(|line| {
+                let mut html: String = stack.as_slice().iter().map(|s| open_span(*s)).collect();
+                let mut position = 0;
+
+                for (index, op) in parser.parse_line(line, &self.syntaxes) {
+                    if index > position {
+                        let _ = escape_html(&mut html, &line[position..index]);
+                        position = index;
+                    }
+                    stack.apply_with_hook(&op, |basic_op, _| match basic_op {
+                        BasicScopeStackOp::Push(scope) => html.push_str(&open_span(scope)),
+                        BasicScopeStackOp::Pop => html.push_str("</span>"),
+                    });
+                }
+
+                let _ = escape_html(&mut html, &line[position..]);
+                html.push_str(&"</span>".repeat(stack.len()));
+                html
+            })
+            .collect()
+    }
+}
+
+fn open_span(scope: Scope) -> String {
+    format!(
+        "<span class=\"{}\">",
+        scope
+            .build_string()
+            .split('.')
+            .map(|atom| format!("{}{}", CLASS_PREFIX, atom))
+            .collect::<Vec<String>>()
+            .join(" ")
+    )
+}
+
+fn escaped(s: &str) -> String {
+    let mut out = String::new();
+    let _ = escape_html(&mut out, s);
+    out
+}
+
+fn css_color(c: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}{:02x}", c.r, c.g, c.b, c.a)
+}
+
+/// stylesheet for `HighlightStyle::Classes` output
+pub fn theme_css(options: &HighlighterOptions) -> AstryxResult<String> {
+    let theme = load_theme(options)?;
+    let mut css = css_for_theme_with_class_style(&theme, CLASS_STYLE);
+
+    css.push_str(".code-line-number {\n user-select: none;\n margin-right: 1em;\n");
+    match theme.settings.gutter_foreground {
+        Some(color) => css.push_str(&format!(" color: {};\n}}\n", css_color(color))),
+        None => css.push_str(" opacity: 0.5;\n}\n"),
     }
 
-    pub fn highlight_line(&self, i: &str) -> String {
-        let s = self
-            .syntaxes
-            .find_syntax_by_extension(&self.syntax)
-            .unwrap();
-        let mut h =
-            HighlightLines::new(s, &self.themes.themes["base16-ocean.dark"]);
-        let regions = h.highlight(i, &self.syntaxes);
-        styled_line_to_highlighted_html(&regions[..], IncludeBackground::No)
+    if let Some(color) = theme.settings.line_highlight {
+        css.push_str(&format!(
+            ".code-line-highlight {{\n display: flex;\n background-color: {};\n}}\n",
+            css_color(color)
+        ));
     }
 
-    // pub fn highlight<'a>(&self, i: &str) -> String {
-    //     format!("{}{}{}", self.start_highlight(), self.highlight_line(i), "")
-    // }
+    Ok(css)
+}
+
+fn load_theme(options: &HighlighterOptions) -> AstryxResult<Theme> {
+    let mut themes = ThemeSet::load_defaults();
+
+    if let Some(dir) = &options.theme_dir {
+        themes.add_from_folder(dir).map_err(|e| {
+            AstryxError::Generic(format!("could not load themes from {}: {}", dir, e))
+        })?;
+    }
+
+    themes.themes.remove(&options.theme).ok_or_else(|| {
+        AstryxError::Generic(format!(
+            "unknown theme: {} (available: {})",
+            options.theme,
+            themes
+                .themes
+                .keys()
+                .cloned()
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))
+    })
+}
+
+fn load_syntaxes(options: &HighlighterOptions) -> AstryxResult<SyntaxSet> {
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+
+    match &options.syntax_dir {
+        Some(dir) => {
+            let mut builder = syntaxes.into_builder();
+            builder.add_from_folder(dir, true).map_err(|e| {
+                AstryxError::Generic(format!("could not load syntaxes from {}: {}", dir, e))
+            })?;
+            Ok(builder.build())
+        }
+        None => Ok(syntaxes),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_code_block_info() {
+        assert_eq!(CodeBlockInfo::parse(""), CodeBlockInfo::default());
+        assert_eq!(
+            CodeBlockInfo::parse("rust").language,
+            Some(String::from("rust"))
+        );
+
+        let info = CodeBlockInfo::parse("rust {3-5,8, linenos}");
+        assert_eq!(info.language, Some(String::from("rust")));
+        assert_eq!(info.highlighted, vec![(3, 5), (8, 8)]);
+        assert!(info.line_numbers);
+        assert!(info.is_highlighted(4));
+        assert!(!info.is_highlighted(6));
+    }
+
+    #[test]
+    fn test_multiline_block() {
+        let mut h = SyntaxHighlighter::new(&HighlighterOptions {
+            style: HighlightStyle::Classes,
+            ..Default::default()
+        })
+        .unwrap();
+
+        // pulldown-cmark can split a block over several text events
+        h.start_block("rust");
+        h.push_text("/* a\n");
+        h.push_text("b */\n");
+        let html = h.end_block();
+
+        assert!(!h.is_highlighting());
+        assert_eq!(
+            html.matches("<span").count(),
+            html.matches("</span>").count()
+        );
+        // the comment scope is carried onto the second line
+        assert_eq!(html.matches("hl-comment hl-block").count(), 2);
+    }
 }
@@ -1,38 +1,249 @@
+mod headings;
 mod highlighter;
+mod shortcodes;
 
+use crate::headings::HeadingIds;
+pub use crate::headings::{heading_tree, slugify, Heading, HeadingNode};
 use crate::highlighter::SyntaxHighlighter;
+pub use crate::highlighter::{theme_css, HighlightStyle, HighlighterOptions};
+use crate::shortcodes::{placeholder, Segment};
 use error::AstryxError;
 use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag};
 
-pub fn parse(i: &str) -> Result<String, AstryxError> {
-    let mut h = SyntaxHighlighter::new();
+#[derive(Debug, Clone)]
+pub struct MarkdownOptions {
+    pub highlight: HighlighterOptions,
+    pub tables: bool,
+    pub footnotes: bool,
+    pub strikethrough: bool,
+    pub tasklists: bool,
+    /// curly quotes, en/em dashes and ellipses
+    pub smart_punctuation: bool,
+    /// give every heading an `id` slugified from its text
+    pub heading_ids: bool,
+    /// prefix headings with a `#` link to themselves (needs `heading_ids`)
+    pub heading_anchors: bool,
+}
 
-    let tokens = Parser::new_ext(i, Options::empty()).map(|event| match event.clone() {
-        Event::Start(Tag::CodeBlock(ref kind)) => match kind {
-            CodeBlockKind::Fenced(info) => {
-                h.set_syntax_by_file_extension(info);
-                let html = h.start_highlight();
-                Event::Html(html.into())
-            }
-            _ => Event::Html("<pre>".into()),
-        },
-        Event::End(Tag::CodeBlock(_)) => {
-            let html = h.stop_highlight();
-            Event::Html(html.into())
+/// plain CommonMark, so pages render as they did before the extensions could be turned on
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions {
+            highlight: HighlighterOptions::default(),
+            tables: false,
+            footnotes: false,
+            strikethrough: false,
+            tasklists: false,
+            smart_punctuation: false,
+            heading_ids: false,
+            heading_anchors: false,
         }
-        Event::Text(text) => {
-            if h.is_highlighting {
-                Event::Html(h.highlight_line(&text.to_owned()).into())
-            } else {
-                event
+    }
+}
+
+impl MarkdownOptions {
+    fn extensions(&self) -> Options {
+        let mut extensions = Options::empty();
+        extensions.set(Options::ENABLE_TABLES, self.tables);
+        extensions.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        extensions.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        extensions.set(Options::ENABLE_TASKLISTS, self.tasklists);
+        extensions.set(Options::ENABLE_SMART_PUNCTUATION, self.smart_punctuation);
+        extensions
+    }
+}
+
+/// average adult silent reading speed, used for reading time
+const WORDS_PER_MINUTE: usize = 200;
+
+/// a rendered markdown document and what was found in it
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub html: String,
+    /// every heading in document order, see `heading_tree` to nest them
+    pub headings: Vec<Heading>,
+    /// words outside of code blocks
+    pub words: usize,
+}
+
+impl Document {
+    /// estimated reading time in whole minutes
+    pub fn reading_time(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+}
+
+pub fn parse(i: &str, options: &MarkdownOptions) -> Result<String, AstryxError> {
+    render(i, options).map(|document| document.html)
+}
+
+pub fn render(i: &str, options: &MarkdownOptions) -> Result<Document, AstryxError> {
+    render_source(i, &[], options)
+}
+
+/// like `render`, but expands `{{ shortcodes }}` (see `shortcodes`). `shortcode` is given
+/// the source of each call and its rendered body, and returns html to splice in.
+pub fn render_with<F>(
+    i: &str,
+    options: &MarkdownOptions,
+    mut shortcode: F,
+) -> Result<Document, AstryxError>
+where
+    F: FnMut(&str, Option<String>) -> Result<String, AstryxError>,
+{
+    render_segments(
+        &shortcodes::split(i, options.extensions())?,
+        options,
+        &mut shortcode,
+    )
+}
+
+/// takes a shortcode call and its rendered body, returns html
+type ShortcodeFn<'a> = dyn FnMut(&str, Option<String>) -> Result<String, AstryxError> + 'a;
+
+fn render_segments(
+    segments: &[Segment],
+    options: &MarkdownOptions,
+    shortcode: &mut ShortcodeFn,
+) -> Result<Document, AstryxError> {
+    let mut source = String::new();
+    let mut spliced = Vec::new();
+
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => source.push_str(text),
+            Segment::Shortcode { call, body } => {
+                let body = match body {
+                    Some(body) => Some(render_segments(body, options, shortcode)?.html),
+                    None => None,
+                };
+                source.push_str(&placeholder(spliced.len()));
+                spliced.push(shortcode(call, body)?);
             }
         }
-        _ => event,
+    }
+
+    render_source(&source, &spliced, options)
+}
+
+/// `spliced` holds the html for each shortcode placeholder in `i`
+fn render_source(
+    i: &str,
+    spliced: &[String],
+    options: &MarkdownOptions,
+) -> Result<Document, AstryxError> {
+    let mut words = 0;
+    let mut h = SyntaxHighlighter::new(&options.highlight)?;
+    let mut headings = HeadingIds::new(options.heading_ids, options.heading_anchors);
+
+    let tokens = Parser::new_ext(i, options.extensions()).filter_map(|event| match event {
+        Event::Start(Tag::CodeBlock(ref kind)) => {
+            match kind {
+                CodeBlockKind::Fenced(info) => h.start_block(info),
+                CodeBlockKind::Indented => h.start_block(""),
+            };
+            None
+        }
+        Event::End(Tag::CodeBlock(_)) => Some(Event::Html(h.end_block().into())),
+        Event::Text(text) if h.is_highlighting() => {
+            h.push_text(&text);
+            None
+        }
+        Event::Html(html) if !spliced.is_empty() => {
+            Some(Event::Html(splice(&html, spliced).into()))
+        }
+        _ => Some(event),
     });
 
+    let tokens = tokens
+        .inspect(|event| {
+            if let Event::Text(text) | Event::Code(text) = event {
+                words += text.split_whitespace().count();
+            }
+        })
+        .flat_map(|event| headings.push(event));
+
     // Write to String buffer.
     let mut html_output = String::new();
     html::push_html(&mut html_output, tokens);
 
-    Ok(html_output)
+    Ok(Document {
+        html: html_output,
+        headings: headings.headings,
+        words,
+    })
+}
+
+fn splice(html: &str, spliced: &[String]) -> String {
+    spliced
+        .iter()
+        .enumerate()
+        .fold(html.to_string(), |html, (index, shortcode)| {
+            html.replace(&placeholder(index), shortcode)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_with_shortcodes() {
+        let document = render_with(
+            "# Title\n\n{{ callout(kind: \"warn\") }}\n*careful*\n{{ end }}\n\ninline {{ x() }} call\n",
+            &MarkdownOptions::default(),
+            |call, body| Ok(format!("[{}|{}]", call, body.unwrap_or_default().trim())),
+        )
+        .unwrap();
+
+        assert!(document
+            .html
+            .contains("[callout(kind: \"warn\")|<p><em>careful</em></p>]"));
+        assert!(document.html.contains("<p>inline [x()|] call</p>"));
+        assert!(!document.html.contains("shortcode:"));
+    }
+
+    #[test]
+    fn test_render_shortcodes_in_code() {
+        let document = render_with(
+            "```\n{{ x }}\n```\n\n`{{ y }}` {{ z() }}\n",
+            &MarkdownOptions::default(),
+            |call, _| Ok(format!("[{}]", call)),
+        )
+        .unwrap();
+
+        assert!(document.html.contains("{{ x }}"));
+        assert!(document.html.contains("<code>{{ y }}</code> [z()]"));
+        assert!(!document.html.contains("shortcode:"));
+    }
+
+    #[test]
+    fn test_render_extensions() {
+        let source = "# Title\n\n| a |\n|---|\n| b |\n\n~~gone~~ \"quoted\"\n";
+
+        let plain = render(source, &MarkdownOptions::default()).unwrap();
+        assert!(plain.html.starts_with("<h1>Title</h1>"));
+        assert!(!plain.html.contains("<table>"));
+        assert!(plain.html.contains("~~gone~~ &quot;quoted&quot;"));
+
+        let options = MarkdownOptions {
+            tables: true,
+            strikethrough: true,
+            smart_punctuation: true,
+            heading_ids: true,
+            ..MarkdownOptions::default()
+        };
+        let extended = render(source, &options).unwrap();
+        assert!(extended.html.starts_with("<h1 id=\"title\">Title</h1>"));
+        assert!(extended.html.contains("<table>"));
+        assert!(extended
+            .html
+            .contains("<del>gone</del> \u{201c}quoted\u{201d}"));
+    }
+
+    #[test]
+    fn test_render_ignores_shortcodes() {
+        let document = render("{{ x() }}", &MarkdownOptions::default()).unwrap();
+        assert_eq!(document.html, "<p>{{ x() }}</p>\n");
+    }
 }
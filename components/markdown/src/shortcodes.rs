@@ -0,0 +1,177 @@
+// shortcodes: function calls embedded in markdown, eg.
+//
+//   {{ video(id: "xyz") }}
+//   {{ callout(kind: "warn") }}markdown *body*{{ end }}
+//
+// `{{ end }}` closes the nearest open shortcode, which then receives its rendered
+// body. shortcodes never closed are inline. an inline shortcode inside a body would
+// take the body's `{{ end }}`, so give it an empty body instead: `{{ video() }}{{ end }}`
+//
+// braces in code blocks and inline code are left as they are.
+
+use error::{AstryxError, AstryxResult};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use std::ops::Range;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+const END: &str = "end";
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Segment<'a> {
+    Text(&'a str),
+    Shortcode {
+        /// the source between the braces, eg. `callout(kind: "warn")`
+        call: &'a str,
+        body: Option<Vec<Segment<'a>>>,
+    },
+}
+
+/// an html comment so pulldown-cmark passes it through as an html event
+pub(crate) fn placeholder(index: usize) -> String {
+    format!("<!--shortcode:{}-->", index)
+}
+
+/// `i` as text and shortcodes, parsing it as markdown with `extensions` to find the code in it
+pub(crate) fn split(i: &str, extensions: Options) -> AstryxResult<Vec<Segment<'_>>> {
+    let code = code_ranges(i, extensions);
+    // the root, then one frame per open shortcode
+    let mut stack: Vec<(Option<&str>, Vec<Segment>)> = vec![(None, Vec::new())];
+    // the start of the text not yet pushed, and where to look for the next shortcode
+    let mut text = 0;
+    let mut from = 0;
+
+    while let Some(found) = i[from..].find(OPEN) {
+        let start = from + found;
+        if let Some(range) = code.iter().find(|range| range.contains(&start)) {
+            from = range.end;
+            continue;
+        }
+
+        let end = i[start..].find(CLOSE).ok_or_else(|| {
+            AstryxError::Generic(format!(
+                "unclosed shortcode: {}",
+                i[start..].lines().next().unwrap_or_default()
+            ))
+        })? + start;
+
+        let call = i[start + OPEN.len()..end].trim();
+        push_text(&mut stack, &i[text..start]);
+        text = end + CLOSE.len();
+        from = text;
+
+        if call != END {
+            stack.push((Some(call), Vec::new()));
+            continue;
+        }
+
+        match stack.pop() {
+            Some((Some(call), body)) => last(&mut stack).push(Segment::Shortcode {
+                call,
+                body: Some(body),
+            }),
+            _ => {
+                return Err(AstryxError::Generic(format!(
+                    "{{{{ {} }}}} without an open shortcode",
+                    END
+                )))
+            }
+        }
+    }
+
+    push_text(&mut stack, &i[text..]);
+
+    // anything left open was an inline shortcode, followed by plain content
+    while stack.len() > 1 {
+        if let Some((Some(call), content)) = stack.pop() {
+            let parent = last(&mut stack);
+            parent.push(Segment::Shortcode { call, body: None });
+            parent.extend(content);
+        }
+    }
+
+    Ok(stack.pop().map(|(_, root)| root).unwrap_or_default())
+}
+
+/// where the code blocks and inline code are in a markdown document
+fn code_ranges(i: &str, extensions: Options) -> Vec<Range<usize>> {
+    Parser::new_ext(i, extensions)
+        .into_offset_iter()
+        .filter(|(event, _)| matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Code(_)))
+        .map(|(_, range)| range)
+        .collect()
+}
+
+fn push_text<'a>(stack: &mut Vec<(Option<&'a str>, Vec<Segment<'a>>)>, text: &'a str) {
+    if !text.is_empty() {
+        last(stack).push(Segment::Text(text));
+    }
+}
+
+fn last<'a, 'b>(
+    stack: &'b mut Vec<(Option<&'a str>, Vec<Segment<'a>>)>,
+) -> &'b mut Vec<Segment<'a>> {
+    &mut stack.last_mut().expect("root frame is never popped").1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn split_all(i: &str) -> AstryxResult<Vec<Segment<'_>>> {
+        split(i, Options::empty())
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split_all("plain").unwrap(), vec![Segment::Text("plain")]);
+
+        assert_eq!(
+            split_all("a {{ video(id: 1) }} b").unwrap(),
+            vec![
+                Segment::Text("a "),
+                Segment::Shortcode {
+                    call: "video(id: 1)",
+                    body: None
+                },
+                Segment::Text(" b"),
+            ]
+        );
+
+        assert_eq!(
+            split_all("{{ callout(kind: \"warn\") }}*hi* {{ x() }}{{ end }}{{ end }}").unwrap(),
+            vec![Segment::Shortcode {
+                call: "callout(kind: \"warn\")",
+                body: Some(vec![
+                    Segment::Text("*hi* "),
+                    Segment::Shortcode {
+                        call: "x()",
+                        body: Some(Vec::new())
+                    },
+                ])
+            }]
+        );
+    }
+
+    #[test]
+    fn test_split_code() {
+        let source = "```\n{{ x }}\n```\n\n`{{ y(` and {{ z() }}\n\n    {{ indented\n";
+        assert_eq!(
+            split_all(source).unwrap(),
+            vec![
+                Segment::Text("```\n{{ x }}\n```\n\n`{{ y(` and "),
+                Segment::Shortcode {
+                    call: "z()",
+                    body: None
+                },
+                Segment::Text("\n\n    {{ indented\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_errors() {
+        assert!(split_all("{{ end }}").is_err());
+        assert!(split_all("{{ callout(").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+// the input files a site was rendered from, for caching and incremental builds
+
+use error::{AstryxError, AstryxErrorKind, AstryxResult, Location};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Clone)]
+pub struct Dependencies {
+    /// read outside of any route, so every route may depend on them
+    global: Vec<PathBuf>,
+    /// read while rendering each route, keyed by route path
+    routes: HashMap<String, Vec<PathBuf>>,
+    /// the routes being rendered, innermost last
+    current: Vec<String>,
+    /// routes whose output is already up to date and can be skipped
+    fresh: HashSet<String>,
+    /// render only every `count`th top level route starting from `index`, see `set_shard`
+    shard: Option<(usize, usize)>,
+    /// top level routes seen so far, rendered or not
+    seen: usize,
+    /// where each route this shard renders (or skips as fresh) is, so paths can't be used twice
+    claimed: HashMap<String, Location>,
+}
+
+impl Dependencies {
+    /// an input of every route being rendered. a route nested in another is skipped along with
+    /// it, so the outer route depends on everything the nested one reads too.
+    pub fn track_input(&mut self, path: PathBuf) {
+        if self.current.is_empty() {
+            return self.global.push(path);
+        }
+
+        for route in &self.current {
+            self.routes
+                .entry(route.clone())
+                .or_default()
+                .push(path.clone());
+        }
+    }
+
+    /// inputs tracked from here until `end_route` belong to `route`. a nested route starts with
+    /// what the route around it has read so far, which it may have been given as variables.
+    pub fn start_route(&mut self, route: &str) {
+        let inherited = match self.current.last() {
+            Some(outer) => self.routes.get(outer).cloned().unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        self.routes
+            .entry(route.into())
+            .or_default()
+            .extend(inherited);
+        self.current.push(route.into());
+    }
+
+    /// go back to the route around the one that ends, if there is one
+    pub fn end_route(&mut self) {
+        self.current.pop();
+    }
+
+    /// every input, sorted and without duplicates
+    pub fn inputs(&self) -> Vec<PathBuf> {
+        dedup(
+            self.global
+                .iter()
+                .chain(self.routes.values().flatten())
+                .cloned()
+                .collect(),
+        )
+    }
+
+    pub fn global_inputs(&self) -> Vec<PathBuf> {
+        dedup(self.global.clone())
+    }
+
+    /// the inputs of each rendered route
+    pub fn route_inputs(&self) -> HashMap<String, Vec<PathBuf>> {
+        self.routes
+            .iter()
+            .map(|(route, inputs)| (route.clone(), dedup(inputs.clone())))
+            .collect()
+    }
+
+    /// mark routes as up to date, so they're skipped instead of rendered
+    pub fn set_fresh(&mut self, routes: HashSet<String>) {
+        self.fresh = routes;
+    }
+
+    pub fn is_fresh(&self, route: &str) -> bool {
+        self.fresh.contains(route)
+    }
+
+    /// split routes between `count` evaluations of the same source, rendering only
+    /// the routes belonging to `index`. top level routes are assigned in the order
+    /// they're reached, which is the same for every evaluation, and nested routes
+    /// go with the route they're in (other shards never reach them).
+    pub fn set_shard(&mut self, index: usize, count: usize) {
+        self.shard = Some((index, count));
+    }
+
+    /// call once for every route reached at `location`, returns whether it should be left
+    /// unrendered. it's an error for this shard to reach the same path twice.
+    pub fn skip_route(&mut self, route: &str, location: Location) -> AstryxResult<bool> {
+        if let Some((index, count)) = self.shard.filter(|_| self.current.is_empty()) {
+            let ordinal = self.seen;
+            self.seen += 1;
+
+            if ordinal % count != index {
+                return Ok(true);
+            }
+        }
+
+        if let Some(previous) = self.claimed.get(route) {
+            return Err(duplicate_route(route, previous.clone(), location));
+        }
+        self.claimed.insert(route.into(), location);
+
+        Ok(self.is_fresh(route))
+    }
+
+    /// combine the dependencies of separately rendered shards, which mustn't have
+    /// rendered the same path
+    pub fn merge(&mut self, other: Dependencies) -> AstryxResult<()> {
+        let mut claimed: Vec<(String, Location)> = other.claimed.into_iter().collect();
+        claimed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (route, location) in claimed {
+            if let Some(previous) = self.claimed.get(&route) {
+                return Err(duplicate_route(&route, previous.clone(), location));
+            }
+            self.claimed.insert(route, location);
+        }
+
+        self.global.extend(other.global);
+        for (route, inputs) in other.routes {
+            self.routes.entry(route).or_default().extend(inputs);
+        }
+        self.fresh.extend(other.fresh);
+
+        Ok(())
+    }
+}
+
+/// the error for two routes with the same path, at whichever comes later in the source
+fn duplicate_route(route: &str, a: Location, b: Location) -> AstryxError {
+    let (first, second) = match (a.line, a.column) <= (b.line, b.column) {
+        true => (a, b),
+        false => (b, a),
+    };
+
+    AstryxError::with_loc(second, AstryxErrorKind::DuplicateRoute(route.into()))
+        .with_label(first, "first defined here")
+}
+
+fn dedup(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_route_inputs() {
+        let mut deps = Dependencies::default();
+        deps.track_input("site.astryx".into());
+        deps.start_route("/a");
+        deps.track_input("a.md".into());
+        deps.track_input("a.md".into());
+        deps.end_route();
+        deps.start_route("/b");
+        deps.end_route();
+
+        assert_eq!(deps.global_inputs(), vec![PathBuf::from("site.astryx")]);
+        assert_eq!(deps.route_inputs()["/a"], vec![PathBuf::from("a.md")]);
+        assert!(deps.route_inputs()["/b"].is_empty());
+        assert_eq!(deps.inputs().len(), 2);
+    }
+
+    #[test]
+    fn test_nested_route_inputs() {
+        let mut deps = Dependencies::default();
+        deps.start_route("/");
+        deps.track_input("posts".into());
+        deps.start_route("/post");
+        deps.track_input("post.md".into());
+        deps.end_route();
+        deps.track_input("footer.md".into());
+        deps.end_route();
+
+        assert!(deps.global_inputs().is_empty());
+        assert_eq!(
+            deps.route_inputs()["/post"],
+            vec![PathBuf::from("post.md"), PathBuf::from("posts")]
+        );
+        assert_eq!(deps.route_inputs()["/"].len(), 3);
+    }
+
+    #[test]
+    fn test_shards() {
+        let rendered = |index| {
+            let mut deps = Dependencies::default();
+            deps.set_shard(index, 2);
+            let mut rendered = Vec::new();
+            for route in &["/a", "/b", "/c"] {
+                if !deps.skip_route(route, location(1)).unwrap() {
+                    rendered.push(*route);
+                    // nested routes go with the route they're in
+                    deps.start_route(route);
+                    let nested = format!("{}/nested", route);
+                    assert!(!deps.skip_route(&nested, location(2)).unwrap());
+                    deps.end_route();
+                }
+            }
+            (rendered, deps)
+        };
+
+        let (first, mut merged) = rendered(0);
+        let (second, other) = rendered(1);
+        assert_eq!(first, vec!["/a", "/c"]);
+        assert_eq!(second, vec!["/b"]);
+        assert!(merged.merge(other).is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_routes() {
+        let mut deps = Dependencies::default();
+        assert!(deps.skip_route("/a", location(1)).is_ok());
+        assert!(deps.skip_route("/a", location(2)).is_err());
+
+        // and between shards
+        let mut other = Dependencies::default();
+        assert!(other.skip_route("/b", location(3)).is_ok());
+        let mut merged = Dependencies::default();
+        assert!(merged.skip_route("/b", location(4)).is_ok());
+        assert!(merged.merge(other).is_err());
+    }
+
+    fn location(line: u32) -> Location {
+        let mut location = Location::from(parser::Span::new_extra("route", "site.astryx"));
+        location.line = line;
+        location
+    }
+}
@@ -1,7 +1,9 @@
+pub mod dependencies;
 pub mod object;
 pub mod render;
 pub mod state;
 
+pub use dependencies::*;
 pub use object::*;
 pub use render::*;
 pub use state::*;
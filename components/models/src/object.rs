@@ -14,6 +14,7 @@ pub enum Object {
     None,
     String(String),
     Number(f64),
+    Boolean(bool),
     Path(String),
     HTMLPage(String),
     HTMLElement(HTMLElement),
@@ -42,14 +43,48 @@ impl Object {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
-            Object::Map(_) => unimplemented!(),
+            Object::Map(m) => {
+                let mut entries: Vec<String> = m
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v.borrow().inspect()))
+                    .collect();
+                entries.sort();
+                format!("{{{}}}", entries.join(", "))
+            }
             Object::None => format!("(None)"),
             Object::Number(f) => f.to_string(),
+            Object::Boolean(b) => b.to_string(),
             Object::HTMLElement(e) => format!("{}{}", e.open_tag(), e.close_tag()),
             Object::HTMLPage(p) => format!("@route path={}", p),
             Object::Path(p) => format!("(Path: {})", p),
             Object::File(f) => format!("(File: {})", f),
-            _ => unimplemented!(),
+        }
+    }
+
+    /// what kind of value this is, for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::None => "nothing",
+            Object::String(_) => "a string",
+            Object::Number(_) => "a number",
+            Object::Boolean(_) => "a boolean",
+            Object::Path(_) => "a path",
+            Object::HTMLPage(_) => "a route",
+            Object::HTMLElement(_) => "an element",
+            Object::File(_) => "a file",
+            Object::BuiltinFunction(_) => "a function",
+            Object::Array(_) => "an array",
+            Object::Map(_) => "a map",
+        }
+    }
+
+    /// the text of a value which can be written into an attribute
+    pub fn as_text(&self) -> Option<String> {
+        match self {
+            Object::String(s) | Object::Path(s) | Object::File(s) => Some(s.clone()),
+            Object::Number(n) => Some(n.to_string()),
+            Object::Boolean(b) => Some(b.to_string()),
+            _ => None,
         }
     }
 }
@@ -64,29 +99,10 @@ impl ToString for Object {
             Object::Map(m) => format!("{:?}", m),
             Object::None => format!("(None)"),
             Object::Number(n) => format!("{}", n),
-            Object::HTMLElement(_) => unimplemented!(),
-            Object::HTMLPage(_) => unimplemented!(),
-            Object::Path(p) => p.into(),
-            _ => unimplemented!(),
-        }
-    }
-}
-
-impl Into<String> for Object {
-    fn into(self) -> String {
-        match self {
-            Object::String(s) => s,
-            // Object::FunctionLiteral { params, statements } => format!("({:?})", params),
-            Object::BuiltinFunction(_) => unimplemented!(),
-            Object::Array(_) => unimplemented!(),
-            Object::Map(_) => unimplemented!(),
-            Object::None => unimplemented!(),
-            Object::Number(_) => unimplemented!(),
-            Object::HTMLElement(_) => unimplemented!(),
-            Object::HTMLPage(_) => unimplemented!(),
-            Object::Path(p) => p,
-            Object::File(p) => p,
-            _ => unimplemented!(),
+            Object::Boolean(b) => b.to_string(),
+            Object::HTMLElement(e) => format!("{}{}", e.open_tag(), e.close_tag()),
+            Object::HTMLPage(p) => p.clone(),
+            Object::Path(p) | Object::File(p) => p.clone(),
         }
     }
 }
@@ -94,23 +110,28 @@ impl Into<String> for Object {
 impl From<Yaml> for Object {
     fn from(yaml: Yaml) -> Object {
         match yaml {
-            Yaml::Real(_) => unimplemented!(),
-            Yaml::Integer(_) => unimplemented!(),
+            // yaml keeps the original text of a real, which might not fit in a float
+            Yaml::Real(r) => r.parse().map(Object::Number).unwrap_or(Object::String(r)),
+            Yaml::Integer(i) => Object::Number(i as f64),
             Yaml::String(s) => Object::String(s),
-            Yaml::Boolean(_) => unimplemented!(),
-            Yaml::Array(_) => unimplemented!(),
+            Yaml::Boolean(b) => Object::Boolean(b),
+            Yaml::Array(a) => Object::Array(a.into_iter().map(|v| Node::new(v.into())).collect()),
             Yaml::Hash(lhm) => {
                 let mut h = HashMap::new();
 
                 for (k, v) in lhm {
-                    h.insert(k.into_string().unwrap(), Node::new(v.into()));
+                    // keys like `2020: ...` or `true: ...` are used as text
+                    let key: Object = k.into();
+                    if let Some(key) = key.as_text() {
+                        h.insert(key, Node::new(v.into()));
+                    }
                 }
 
                 Object::Map(h)
             }
-            Yaml::Alias(_) => unimplemented!(),
-            Yaml::Null => unimplemented!(),
-            Yaml::BadValue => unimplemented!(),
+            Yaml::Null => Object::None,
+            // yaml-rust doesn't resolve aliases yet, and only produces BadValue when indexing
+            Yaml::Alias(_) | Yaml::BadValue => Object::None,
         }
     }
 }
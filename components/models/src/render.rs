@@ -4,6 +4,7 @@
 use crate::Object;
 use rctree::Node;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 pub struct Site {
     pub documents: HashMap<String, String>,
@@ -20,18 +21,27 @@ impl Site {
         Site { documents }
     }
 
-    /// todo: supply output path
-    pub fn write(&self) {
+    /// write every page into the `output` directory
+    pub fn write(&self, output: &Path) -> std::io::Result<()> {
         for (hash, document) in &self.documents {
-            let path = format!("./build{}/index.html", hash);
-            let path = std::path::Path::new(&path);
-            let prefix = path.parent().unwrap();
+            let path = Site::document_path(output, hash);
 
-            println!("writing {:?}", path);
+            eprintln!("writing {:?}", path);
 
-            std::fs::create_dir_all(prefix).unwrap();
-            std::fs::write(path, document).unwrap();
+            if let Some(prefix) = path.parent() {
+                std::fs::create_dir_all(prefix)?;
+            }
+            std::fs::write(path, document)?;
         }
+
+        Ok(())
+    }
+
+    /// where the page for a route is written inside `output`
+    pub fn document_path(output: &Path, route: &str) -> PathBuf {
+        output
+            .join(route.trim_start_matches('/'))
+            .join("index.html")
     }
 }
 
@@ -39,19 +49,20 @@ fn walk_nodes(node: Node<Object>, buffer: &mut HashMap<String, String>, mut path
     // entry
     match node.borrow().clone() {
         Object::None => {}
-        Object::String(s) => write_to_buffer(buffer, &path, &s),
-        Object::Number(_) => unimplemented!(),
         Object::HTMLPage(p) => path = p,
         Object::HTMLElement(el) => write_to_buffer(buffer, &path, &el.open_tag()),
-        Object::BuiltinFunction(_) => unimplemented!(),
         Object::Array(arr) => {
             for item in arr {
                 walk_nodes(item, buffer, path.clone());
             }
         }
-        Object::Map(_) => unimplemented!(),
-        Object::Path(_) => unimplemented!(),
-        _ => unimplemented!(),
+        // the interpreter reports these as errors, there's nothing sensible to write
+        Object::Map(_) | Object::BuiltinFunction(_) => {}
+        ref value => {
+            if let Some(text) = value.as_text() {
+                write_to_buffer(buffer, &path, &text)
+            }
+        }
     };
 
     // children
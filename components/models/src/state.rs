@@ -1,13 +1,23 @@
-use crate::object::Object;
-use error::{AstryxError, AstryxErrorKind, AstryxResult, Location};
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use crate::{dependencies::Dependencies, object::Object};
+use error::{suggest, AstryxError, AstryxErrorKind, AstryxResult, Location};
+use std::{cell::RefCell, collections::HashMap, path::PathBuf, rc::Rc};
 
 type LocalData = HashMap<String, Object>;
 
 #[derive(Clone, Default)]
 pub struct State {
     pub local: LocalData,
+    /// where variables bound with `bind_at` were defined
+    locations: HashMap<String, Location>,
     outer: Option<Rc<RefCell<State>>>,
+    /// for function arguments, the scope the function was called from
+    caller: Option<Rc<RefCell<State>>>,
+    /// files and directories read while interpreting, shared by every scope
+    dependencies: Rc<RefCell<Dependencies>>,
+    /// errors reported while recovering, shared by every scope. `None` stops at the first error.
+    errors: Option<Rc<RefCell<Vec<AstryxError>>>>,
+    /// whether source comments are written into pages, passed on to every scope
+    comments: bool,
 }
 
 impl<'a> State {
@@ -15,10 +25,35 @@ impl<'a> State {
     pub fn new() -> Self {
         State {
             local: LocalData::new(),
+            locations: HashMap::new(),
             outer: None,
+            caller: None,
+            dependencies: Default::default(),
+            errors: None,
+            comments: false,
         }
     }
 
+    /// an empty scope for the arguments of a function called from `caller`
+    pub fn call(caller: Rc<RefCell<Self>>) -> Self {
+        let dependencies = caller.borrow().dependencies();
+        let errors = caller.borrow().errors.clone();
+        let comments = caller.borrow().comments;
+
+        Self {
+            dependencies,
+            errors,
+            comments,
+            caller: Some(caller),
+            ..Default::default()
+        }
+    }
+
+    /// the scope a function was called from, for builtins which evaluate code themselves
+    pub fn caller(&self) -> Option<Rc<RefCell<State>>> {
+        self.caller.as_ref().map(Rc::clone)
+    }
+
     pub fn get(&self, name: &str) -> Option<Object> {
         match self.local.get(name) {
             Some(value) => Some(value.clone()),
@@ -32,10 +67,50 @@ impl<'a> State {
     /// fetch a variable from state and throw an error upon failure
     pub fn require<S: Into<Location> + ToString>(&self, ident: S) -> AstryxResult<Object> {
         let i = ident.to_string();
-        self.get(&i).ok_or(AstryxError::LocatedError(
-            ident.into(),
-            AstryxErrorKind::MissingRequiredArgument(i),
-        ))
+        self.get(&i).ok_or_else(|| {
+            AstryxError::with_loc(ident, AstryxErrorKind::MissingRequiredArgument(i))
+        })
+    }
+
+    /// fetch a variable referenced in source, suggesting similar names when it's unknown
+    pub fn resolve<S: Into<Location> + ToString>(&self, ident: S) -> AstryxResult<Object> {
+        let name = ident.to_string();
+        if let Some(value) = self.get(&name) {
+            return Ok(value);
+        }
+
+        let names = self.names();
+        let error = AstryxError::with_loc(ident, AstryxErrorKind::UnknownVariable(name.clone()));
+
+        Err(match suggest(&name, names.iter().map(String::as_str)) {
+            Some(similar) => {
+                let error = error.with_help(format!("did you mean `{}`?", similar));
+                match self.location(similar) {
+                    Some(location) => {
+                        error.with_label(location, &format!("`{}` defined here", similar))
+                    }
+                    None => error,
+                }
+            }
+            None => error,
+        })
+    }
+
+    /// where a variable was defined, if it was bound with `bind_at`
+    pub fn location(&self, name: &str) -> Option<Location> {
+        match self.locations.get(name) {
+            Some(location) => Some(location.clone()),
+            None => self.outer.as_ref().and_then(|o| o.borrow().location(name)),
+        }
+    }
+
+    /// every variable name in scope
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.local.keys().cloned().collect();
+        if let Some(outer) = &self.outer {
+            names.extend(outer.borrow().names());
+        }
+        names
     }
 
     /// bind a variable to local state
@@ -44,13 +119,78 @@ impl<'a> State {
         Ok(()) // force return ok (this could change if mutability rules change, or overwriting builtins)
     }
 
+    /// bind a variable defined in source, remembering where for error messages
+    pub fn bind_at<S: Into<Location> + ToString>(
+        &mut self,
+        ident: S,
+        obj: Object,
+    ) -> AstryxResult<()> {
+        let name = ident.to_string();
+        self.locations.insert(name.clone(), ident.into());
+        self.bind(&name, obj)
+    }
+
     pub fn extend(outer: Rc<RefCell<Self>>) -> Self {
+        let dependencies = outer.borrow().dependencies();
+        let errors = outer.borrow().errors.clone();
+        let comments = outer.borrow().comments;
+
         Self {
+            dependencies,
+            errors,
+            comments,
             outer: Some(outer),
             ..Default::default()
         }
     }
 
+    /// record a file or directory the output depends on
+    pub fn track_input<P: Into<PathBuf>>(&self, path: P) {
+        self.dependencies.borrow_mut().track_input(path.into());
+    }
+
+    /// every input tracked so far, sorted and without duplicates
+    pub fn inputs(&self) -> Vec<PathBuf> {
+        self.dependencies.borrow().inputs()
+    }
+
+    pub fn dependencies(&self) -> Rc<RefCell<Dependencies>> {
+        Rc::clone(&self.dependencies)
+    }
+
+    /// keep interpreting past failing statements, collecting their errors with `report`
+    pub fn recover(&mut self) {
+        self.errors = Some(Default::default());
+    }
+
+    pub fn recovering(&self) -> bool {
+        self.errors.is_some()
+    }
+
+    /// write source comments into pages as html comments
+    pub fn emit_comments(&mut self) {
+        self.comments = true;
+    }
+
+    pub fn emitting_comments(&self) -> bool {
+        self.comments
+    }
+
+    /// record an error to carry on from while recovering
+    pub fn report(&self, error: AstryxError) {
+        if let Some(errors) = &self.errors {
+            errors.borrow_mut().push(error);
+        }
+    }
+
+    /// every error reported so far, in the order they happened
+    pub fn take_errors(&self) -> Vec<AstryxError> {
+        self.errors
+            .as_ref()
+            .map(|errors| std::mem::take(&mut *errors.borrow_mut()))
+            .unwrap_or_default()
+    }
+
     /// returns a flattened hashmap of all objects in state
     pub fn to_map(&self) -> HashMap<String, Object> {
         self.local.clone() // todo: inherit
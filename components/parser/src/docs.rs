@@ -0,0 +1,68 @@
+// documentation written in `---` comments, for the language server and anything else that lists
+// what a document declares:
+//
+//     --- the title of every page
+//     --- (and the feed)
+//     let title = "My Blog"
+
+use crate::{Span, Statement};
+use rctree::Node;
+
+/// a `let` with the doc comments directly above it
+#[derive(Debug, Clone)]
+pub struct Documented<'a> {
+    pub ident: Span<'a>,
+    /// the comments a line each, without `---` or the space after it
+    pub doc: String,
+}
+
+/// every documented `let` in `nodes` and their children, in the order they're written
+pub fn documented<'a>(nodes: &[Node<Statement<'a>>]) -> Vec<Documented<'a>> {
+    let mut documented = Vec::new();
+    collect(nodes.iter().cloned(), &mut documented);
+    documented
+}
+
+fn collect<'a, I: Iterator<Item = Node<Statement<'a>>>>(
+    nodes: I,
+    documented: &mut Vec<Documented<'a>>,
+) {
+    let mut doc: Vec<&str> = Vec::new();
+
+    for node in nodes {
+        match node.borrow().clone() {
+            Statement::DocComment(line) => {
+                let line = line.fragment().trim_end();
+                doc.push(line.strip_prefix(' ').unwrap_or(line));
+                continue;
+            }
+            Statement::Binding(ident, _) if !doc.is_empty() => documented.push(Documented {
+                ident,
+                doc: doc.join("\n"),
+            }),
+            _ => {}
+        }
+
+        // doc comments only go with the statement right after them
+        doc.clear();
+        collect(node.children(), documented);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_documented() {
+        let source = "--- the site's name\n---\n---  shown everywhere\nlet title = \"a\"\n--- not a let\n%p\nlet b = 1\n@route path=\"/\"\n  --- in a route\n  let c = 2\n";
+        let (nodes, _) = crate::run_all(source, "");
+        let documented = documented(&nodes);
+
+        assert_eq!(documented.len(), 2);
+        assert_eq!(documented[0].ident.fragment(), &"title");
+        assert_eq!(documented[0].doc, "the site's name\n\n shown everywhere");
+        assert_eq!(documented[1].ident.location_line(), 10);
+        assert_eq!(documented[1].doc, "in a route");
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
-    error::ParserErrorKind, statement::expression, text::tokenised_string, Element, Expression,
-    ParserError, Span,
+    error::ParserErrorKind,
+    statement::expression,
+    text::tokenised_string,
+    whitespace::{list_end, ws0},
+    Element, Expression, ParserError, Span, TextBlock,
 };
 use nom::{
     bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric1, multispace0},
-    character::complete::{char, space0},
-    combinator::{cut, opt},
+    character::complete::{alpha1, alphanumeric1},
+    character::complete::{char, multispace0, space0},
+    combinator::{all_consuming, cut, opt},
+    error::ParseError,
     multi::separated_list0,
     sequence::{preceded, terminated, tuple},
     IResult,
@@ -19,7 +23,7 @@ fn attributes_braced<'a>(
         char('{'),
         cut(terminated(
             separated_list0(char(','), attribute_assignment),
-            char('}'),
+            list_end('}'),
         )),
     )(i)
 }
@@ -28,12 +32,12 @@ fn attribute_assignment<'a>(
     i: Span<'a>,
 ) -> IResult<Span<'a>, (Span<'a>, Expression), ParserError<Span<'a>>> {
     nom::sequence::tuple((
-        multispace0,
+        ws0,
         alpha1,
-        terminated(multispace0, char(':')),
-        space0,
+        terminated(ws0, char(':')),
+        ws0,
         cut(expression),
-        space0,
+        ws0,
     ))(i)
     .map(|(r, (_, ident, _, _, value, _))| (r, (ident, value)))
 }
@@ -42,18 +46,23 @@ pub(crate) fn element<'a>(i: Span<'a>) -> IResult<Span<'a>, Element<'a>, ParserE
     tuple((
         tag("%"),
         alphanumeric1,
-        space0,
+        ws0,
         opt(attributes_braced),
         space0,
+        opt(raw_marker),
         opt(tokenised_string),
     ))(i)
-    .map(|(r, (_, ident, _, attributes, _, text))| {
+    .map(|(r, (_, ident, _, attributes, _, raw, text))| {
         (
             r,
             Element {
                 ident,
                 attributes: attributes.unwrap_or(vec![]),
                 text,
+                block: raw.map(|_| TextBlock {
+                    lines: Vec::new(),
+                    interpolated: false,
+                }),
             },
         )
     })
@@ -65,3 +74,32 @@ pub(crate) fn element<'a>(i: Span<'a>) -> IResult<Span<'a>, Element<'a>, ParserE
         })
     })
 }
+
+/// `:raw` straight after an element's name or attributes, with nothing after it (e.g.
+/// `%script :raw`), takes the lines indented under the element as text, see `linesplit`
+fn raw_marker<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> IResult<Span<'a>, Span<'a>, E> {
+    terminated(tag(":raw"), all_consuming(multispace0))(i)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_element() {
+        let (_, script) =
+            element(Span::new_extra("%script { type: \"module\" } :raw", "")).unwrap();
+        assert_eq!(script.attributes.len(), 1);
+        assert!(script.block.is_some());
+
+        let (_, p) = element(Span::new_extra("%p some text", "")).unwrap();
+        assert!(p.block.is_none());
+
+        // only straight after the name or attributes, and with nothing after it
+        for text in &["%p set the flag to :raw", "%p :raw text", "%p {} text :raw"] {
+            let (_, p) = element(Span::new_extra(text, "")).unwrap();
+            assert!(p.block.is_none());
+            assert!(p.text.is_some());
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::Span;
-use nom::error::{ErrorKind, ParseError};
+use nom::error::{ErrorKind, FromExternalError, ParseError};
 
 #[derive(Debug)]
 pub struct ParserError<I> {
@@ -23,6 +23,21 @@ impl<'a> ParseError<Span<'a>> for ParserError<Span<'a>> {
     }
 }
 
+/// for parsers generic over their error type which fail with one of our kinds
+impl<'a> FromExternalError<Span<'a>, ParserErrorKind<Span<'a>>> for ParserError<Span<'a>> {
+    fn from_external_error(
+        input: Span<'a>,
+        _kind: ErrorKind,
+        kind: ParserErrorKind<Span<'a>>,
+    ) -> Self {
+        ParserError {
+            kind,
+            pos: input,
+            context: input,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ParserErrorKind<I> {
     SyntaxError,
@@ -30,5 +45,14 @@ pub enum ParserErrorKind<I> {
     Unexpected,
     UnexpectedToken(String),
     ExpectedValue,
+    /// indented with something other than whole levels of the file's indent unit
+    InconsistentIndentation {
+        unit: String,
+        found: String,
+    },
+    /// more than one level deeper than the line above
+    OverIndented,
+    /// `--[` without a `]--`
+    UnclosedComment,
     Nom(I),
 }
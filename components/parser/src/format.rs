@@ -0,0 +1,138 @@
+// writes a document back out in one style: two spaces for each level of indentation, at most
+// one blank line between statements (and none at the start of a block), and every statement as
+// `Statement::inspect` writes it, except that long elements have an attribute to a line. text and
+// comments are kept as they are (block comments only have their first line indented).
+
+use crate::{
+    models::inspect_tokens, run_lossless, Element, ParserError, SourceLine, Span, Statement,
+    StringToken,
+};
+
+const INDENT: &str = "  ";
+/// elements longer than this are written with an attribute to a line
+const MAX_WIDTH: usize = 100;
+
+/// the formatted document, or every error if any of it doesn't parse (lines that fail would
+/// otherwise be lost)
+pub fn format<'a>(i: &'a str, filename: &'a str) -> Result<String, Vec<ParserError<Span<'a>>>> {
+    let (lines, errors) = run_lossless(i, filename);
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut output = String::new();
+    write_lines(&mut output, &lines, 0);
+    Ok(output)
+}
+
+fn write_lines(output: &mut String, lines: &[SourceLine], depth: usize) {
+    for (n, line) in lines.iter().enumerate() {
+        if n > 0 && line.blank_lines > 0 {
+            output.push('\n');
+        }
+
+        let indent = INDENT.repeat(depth);
+        let statement = line.statement.inspect();
+
+        match &line.statement {
+            Statement::Element(element)
+                if !element.attributes.is_empty()
+                    && indent.len() + statement.chars().count() > MAX_WIDTH =>
+            {
+                write_long_element(output, element, &indent)
+            }
+            _ => {
+                output.push_str(&indent);
+                output.push_str(&statement);
+            }
+        }
+        output.push('\n');
+
+        if let Some(block) = line.statement.text_block() {
+            write_block(output, &block.lines, &indent);
+        }
+
+        write_lines(output, &line.children, depth + 1);
+    }
+}
+
+/// an element with its attributes on their own lines, between braces on the element's lines
+fn write_long_element(output: &mut String, element: &Element, indent: &str) {
+    output.push_str(&format!("{}%{} {{\n", indent, element.ident));
+
+    for (key, value) in &element.attributes {
+        output.push_str(&format!(
+            "{}{}{}: {},\n",
+            indent,
+            INDENT,
+            key,
+            value.inspect()
+        ));
+    }
+
+    output.push_str(&format!("{}}}", indent));
+    if let Some(text) = element.text.as_ref().filter(|text| !text.is_empty()) {
+        output.push(' ');
+        output.push_str(&inspect_tokens(text));
+    }
+}
+
+/// the lines of a text block, a level in from the statement they're under
+fn write_block(output: &mut String, lines: &[Vec<StringToken>], indent: &str) {
+    for line in lines {
+        if !line.is_empty() {
+            output.push_str(indent);
+            output.push_str(INDENT);
+            output.push_str(&inspect_tokens(line));
+        }
+        output.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let source = "\n\n-- a comment   \nlet  title\t= \"hello\"\nlet n=1.50\n\n\n@route   path=\"/\"\n\n\t%div {class:\"a\",id: \"b\"}   some ${ title } text\n\t\t%p ${title} ${n}\n\n\t\tfor post in ../posts/*.md\n\t\t\tpost.markdown()\n\t%hr {}\n\t| piped\n";
+        let formatted = "-- a comment\nlet title = \"hello\"\nlet n = 1.50\n\n@route path=\"/\"\n  %div { class: \"a\", id: \"b\" } some ${title} text\n    %p ${title} ${n}\n\n    for post in ../posts/*.md\n      post.markdown()\n  %hr\n  | piped\n";
+
+        assert_eq!(format(source, "").unwrap(), formatted);
+        assert_eq!(format(formatted, "").unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_long_elements() {
+        let source = "%a {\n  href: \"/\"\n}\n%body\n  %link { rel: \"stylesheet\", href: \"https://fonts.example.com/css?family=Some+Font\", media: \"all\" } text\n";
+        let formatted = "%a { href: \"/\" }\n%body\n  %link {\n    rel: \"stylesheet\",\n    href: \"https://fonts.example.com/css?family=Some+Font\",\n    media: \"all\",\n  } text\n";
+
+        assert_eq!(format(source, "").unwrap(), formatted);
+        assert_eq!(format(formatted, "").unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_text_blocks() {
+        let source = "%body\n\t%script   :raw\n\t\t\tif (a) {\n\t\t\t\tb(`${c}`);\n\n\t\t\t}\n\n\t|||\n\t\t  ${ title }\n";
+        let formatted = "%body\n  %script :raw\n    if (a) {\n    \tb(`${c}`);\n\n    }\n\n  |||\n    ${title}\n";
+
+        assert_eq!(format(source, "").unwrap(), formatted);
+        assert_eq!(format(formatted, "").unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let source = "---  the title  \nlet title = 1\n%div\n    --[\n  %p\n    ]--\n    %p\n";
+        let formatted = "---  the title\nlet title = 1\n%div\n  --[\n  %p\n    ]--\n  %p\n";
+
+        assert_eq!(format(source, "").unwrap(), formatted);
+        assert_eq!(format(formatted, "").unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_errors() {
+        assert_eq!(format("%p ok\n!\n]\n", "").unwrap_err().len(), 2);
+        assert_eq!(format("", "").unwrap(), "");
+    }
+}
@@ -1,9 +1,12 @@
 use crate::{
-    error::ParserErrorKind, statement::expression, Expression, FunctionCall, ParserError, Span,
+    error::ParserErrorKind,
+    statement::expression,
+    whitespace::{list_end, ws0},
+    Expression, FunctionCall, ParserError, Span,
 };
 use nom::{
-    character::complete::{alpha1, multispace0},
-    character::complete::{char, space0},
+    character::complete::alpha1,
+    character::complete::char,
     combinator::cut,
     multi::separated_list0,
     sequence::{terminated, tuple},
@@ -13,20 +16,15 @@ use nom::{
 fn function_call_argument<'a>(
     i: Span<'a>,
 ) -> IResult<Span<'a>, (Span<'a>, Expression<'a>), ParserError<Span<'a>>> {
-    tuple((
-        alpha1,
-        terminated(multispace0, char(':')),
-        space0,
-        cut(expression),
-    ))(i)
-    .map(|(r, (ident, _, _, value))| (r, (ident, value)))
-    .map_err(|e: nom::Err<_>| {
-        e.map(|e| ParserError {
-            context: i,
-            kind: ParserErrorKind::ExpectedValue,
-            pos: e.context,
+    tuple((alpha1, terminated(ws0, char(':')), ws0, cut(expression)))(i)
+        .map(|(r, (ident, _, _, value))| (r, (ident, value)))
+        .map_err(|e: nom::Err<_>| {
+            e.map(|e| ParserError {
+                context: i,
+                kind: ParserErrorKind::ExpectedValue,
+                pos: e.context,
+            })
         })
-    })
 }
 
 fn function_call_arguments<'a>(
@@ -34,7 +32,7 @@ fn function_call_arguments<'a>(
 ) -> IResult<Span<'a>, Vec<(Span<'a>, Expression<'a>)>, ParserError<Span<'a>>> {
     // many0(function_call_argument)(i)
 
-    separated_list0(tuple((space0, char(','), space0)), function_call_argument)(i)
+    separated_list0(tuple((ws0, char(','), ws0)), function_call_argument)(i)
     // .map_err(|e:nom::Err<ParserError<_>>| {
     //     e.map(|s| ParserError {
     //         context: i,
@@ -47,17 +45,22 @@ fn function_call_arguments<'a>(
 pub(crate) fn function_call<'a>(
     i: Span<'a>,
 ) -> IResult<Span<'a>, FunctionCall<'a>, ParserError<Span<'a>>> {
-    tuple((alpha1, char('('), function_call_arguments, cut(char(')'))))(i).map(
-        |(r, (ident, _, arguments, _))| {
-            (
-                r,
-                FunctionCall {
-                    ident: Box::new(Expression::Reference(ident)),
-                    arguments,
-                },
-            )
-        },
-    )
+    tuple((
+        alpha1,
+        char('('),
+        ws0,
+        function_call_arguments,
+        cut(list_end(')')),
+    ))(i)
+    .map(|(r, (ident, _, _, arguments, _))| {
+        (
+            r,
+            FunctionCall {
+                ident: Box::new(Expression::Reference(ident)),
+                arguments,
+            },
+        )
+    })
     // .map_err(|e| {
     //     e.map(|s| ParserError {
     //         context: i,
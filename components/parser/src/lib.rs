@@ -19,14 +19,19 @@
 use linesplit::Line;
 use nom_locate::LocatedSpan;
 
-use nom::{Err, IResult};
+use nom::{
+    error::{ErrorKind, ParseError},
+    Err, IResult,
+};
 use rctree::Node;
 
 pub type Span<'a> = LocatedSpan<&'a str, &'a str>;
 pub type ParserResult<T, I> = Result<T, ParserError<I>>;
 
+pub mod docs;
 mod element;
 pub mod error;
+pub mod format;
 mod function;
 mod linesplit;
 pub mod models;
@@ -42,34 +47,96 @@ pub fn run<'a>(
     i: &'a str,
     filename: &'a str,
 ) -> Result<Vec<Node<Statement<'a>>>, ParserError<Span<'a>>> {
-    // let span = Span::new(i);
-    let (_, lines): (_, Vec<Line>) =
-        linesplit::take_lines(Span::new_extra(i, filename)).expect("linesplit fail (fix)"); // break document up by whitespace indentation
+    let (nodes, mut errors) = run_all(i, filename);
 
-    lines
+    if errors.is_empty() {
+        Ok(nodes)
+    } else {
+        Err(errors.remove(0))
+    }
+}
+
+/// parse every line of a document, recovering from errors by skipping the failing line
+/// (and anything indented under it). returns the statements that parsed and every error.
+pub fn run_all<'a>(
+    i: &'a str,
+    filename: &'a str,
+) -> (Vec<Node<Statement<'a>>>, Vec<ParserError<Span<'a>>>) {
+    let (lines, errors) = run_lossless(i, filename);
+    let nodes = lines.into_iter().map(SourceLine::into_node).collect();
+
+    (nodes, errors)
+}
+
+/// parse a document like `run_all`, but keep the blank lines between statements
+pub fn run_lossless<'a>(
+    i: &'a str,
+    filename: &'a str,
+) -> (Vec<SourceLine<'a>>, Vec<ParserError<Span<'a>>>) {
+    let mut errors = Vec::new();
+
+    // break document up by whitespace indentation
+    let lines: Vec<Line> = match linesplit::take_lines(Span::new_extra(i, filename)) {
+        Ok((rest, (lines, indentation_errors))) => {
+            errors.extend(indentation_errors);
+            if !rest.fragment().trim().is_empty() {
+                errors.push(ParserError::from_error_kind(rest, ErrorKind::Eof));
+            }
+            lines
+        }
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            errors.push(ParserError::from_error_kind(e.input, e.code));
+            Vec::new()
+        }
+        Err(Err::Incomplete(_)) => Vec::new(),
+    };
+
+    let source_lines = lines
         .into_iter()
-        .map(parse_line)
-        .collect::<Result<Vec<(Span, Node<Statement<'_>>)>, nom::Err<ParserError<Span<'a>>>>>()
-        .map_err(|e| match e {
-            // convert to a regular result, nom is awful in this situation.
-            Err::Error(e) | Err::Failure(e) => e,
-            _ => unreachable!(),
-        })
-        // now we need to get rid of the remainder inside the result (I know, all of this is messy,
-        // but it's isolated to this one function jumping from nom-style to rust-style.
-        .map(|result| result.into_iter().map(|(_, nodes)| nodes).collect())
+        .filter_map(|line| parse_line(line, &mut errors))
+        .collect();
+
+    (source_lines, errors)
 }
 
-fn parse_line<'a>(line: Line<'a>) -> IResult<Span<'a>, Node<Statement<'a>>, ParserError<Span<'a>>> {
-    let (r, statement) = statement::statement(line.content)?;
-    let mut node: Node<Statement> = Node::new(statement);
+fn parse_line<'a>(
+    line: Line<'a>,
+    errors: &mut Vec<ParserError<Span<'a>>>,
+) -> Option<SourceLine<'a>> {
+    let mut statement = match statement::statement(line.content) {
+        Ok((_, statement)) => statement,
+        // convert to a regular error, nom is awful in this situation.
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            errors.push(e);
+            return None;
+        }
+        // only streaming parsers are incomplete
+        Err(Err::Incomplete(_)) => {
+            errors.push(ParserError::from_error_kind(line.content, ErrorKind::Eof));
+            return None;
+        }
+    };
 
-    for child in line.children {
-        let (_, child_node) = parse_line(child)?;
-        node.append(child_node);
+    if let Some(block) = statement.text_block_mut() {
+        let interpolated = block.interpolated;
+        block.lines = line
+            .block
+            .into_iter()
+            .filter_map(|span| text::block_line(span, interpolated, errors))
+            .collect();
     }
 
-    Ok((r, node))
+    let children = line
+        .children
+        .into_iter()
+        .filter_map(|child| parse_line(child, errors))
+        .collect();
+
+    Some(SourceLine {
+        statement,
+        blank_lines: line.blank_lines,
+        children,
+    })
 }
 
 pub fn parse<'a>(i: Span<'a>) -> IResult<Span<'a>, Statement<'a>, ParserError<Span<'a>>> {
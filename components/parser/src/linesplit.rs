@@ -1,38 +1,181 @@
-use crate::Span;
+use crate::{element::element, error::ParserErrorKind, Element, ParserError, Span};
 use nom::{
-    bytes::complete::is_not,
     character::complete::{newline, one_of, space0},
-    combinator::{cut, opt},
-    multi::many0,
+    combinator::{cut, opt, recognize},
+    error::{Error, ErrorKind},
+    multi::{many0, many0_count},
     sequence::tuple,
-    IResult,
+    Err, IResult, InputTake, Slice,
 };
+use std::iter::Peekable;
 
 #[derive(Debug)]
 pub struct Line<'a> {
     pub content: Span<'a>,
     pub children: Vec<Line<'a>>,
+    /// blank lines between this line and the one before it
+    pub blank_lines: usize,
+    /// for `|||` and elements marked `:raw`, the lines indented under it, as text
+    pub block: Vec<Span<'a>>,
+}
+
+/// a line before it's been nested under its parent
+struct RawLine<'a> {
+    blank_lines: usize,
+    indent: Span<'a>,
+    content: Span<'a>,
+    block: Vec<Span<'a>>,
 }
 
 // pub(crate) fn take_lines<'a>(i: &'a str) -> IResult<Span<'a>, Vec<Line<'a>>> {
 //     cut(many0(take_children))(Span::new(&i))
 // }
 
-pub(crate) fn take_lines<'a>(i: Span<'a>) -> IResult<Span<'a>, Vec<Line<'a>>> {
-    cut(many0(take_children))(i)
+/// split a document into lines nested by indentation. every line must be indented by a whole
+/// number of the file's indent unit (whatever the first indented line uses), and by at most one
+/// more than the line above. lines that aren't are returned as errors and left out, along with
+/// anything indented under them.
+pub(crate) fn take_lines<'a>(
+    i: Span<'a>,
+) -> IResult<Span<'a>, (Vec<Line<'a>>, Vec<ParserError<Span<'a>>>)> {
+    let (r, raw_lines) = cut(many0(line))(i)?;
+
+    let mut errors = Vec::new();
+    let mut lines = Vec::new();
+    let mut unit: Option<&str> = None;
+    let mut previous_depth: Option<usize> = None;
+    // the indentation of a rejected line, which lines indented further belong to
+    let mut rejected: Option<usize> = None;
+
+    for raw in raw_lines {
+        let width = raw.indent.fragment().len();
+        match rejected {
+            Some(rejected) if width > rejected => continue,
+            _ => rejected = None,
+        }
+
+        let max_depth = previous_depth.map_or(0, |depth| depth + 1);
+        let error = match depth(raw.indent.fragment(), &mut unit) {
+            Ok(depth) if depth <= max_depth => {
+                previous_depth = Some(depth);
+                lines.push((depth, raw));
+                continue;
+            }
+            Ok(_) => ParserError {
+                kind: ParserErrorKind::OverIndented,
+                pos: raw.content,
+                context: raw.content,
+            },
+            Err(kind) => ParserError {
+                kind,
+                pos: raw.indent,
+                context: raw.indent,
+            },
+        };
+
+        errors.push(error);
+        rejected = Some(width);
+    }
+
+    Ok((r, (nest(&mut lines.into_iter().peekable(), 0), errors)))
+}
+
+/// how many indent units deep a line is, taking the unit from the first indented line
+fn depth<'a>(
+    indent: &'a str,
+    unit: &mut Option<&'a str>,
+) -> Result<usize, ParserErrorKind<Span<'a>>> {
+    let indent_unit = match (indent.is_empty(), *unit) {
+        (true, _) => return Ok(0),
+        (false, Some(unit)) => unit,
+        (false, None) => {
+            // if the first indent is mixed, its first run of tabs or spaces
+            let first = indent.chars().next().unwrap_or(' ');
+            let run = indent.chars().take_while(|c| *c == first).count();
+            *unit = Some(&indent[..run]);
+            &indent[..run]
+        }
+    };
+
+    let consistent = indent
+        .as_bytes()
+        .chunks(indent_unit.len())
+        .all(|level| level == indent_unit.as_bytes());
+
+    match consistent {
+        true => Ok(indent.len() / indent_unit.len()),
+        false => Err(ParserErrorKind::InconsistentIndentation {
+            unit: describe_indent(indent_unit),
+            found: describe_indent(indent),
+        }),
+    }
+}
+
+/// "a tab", "2 spaces", "a mix of tabs and spaces"...
+fn describe_indent(indent: &str) -> String {
+    let tabs = indent.matches('\t').count();
+    match (tabs, indent.len() - tabs) {
+        (1, 0) => String::from("a tab"),
+        (tabs, 0) => format!("{} tabs", tabs),
+        (0, 1) => String::from("a space"),
+        (0, spaces) => format!("{} spaces", spaces),
+        _ => String::from("a mix of tabs and spaces"),
+    }
+}
+
+/// the lines at `depth`, each with the deeper lines after it as children
+fn nest<'a, I: Iterator<Item = (usize, RawLine<'a>)>>(
+    lines: &mut Peekable<I>,
+    depth: usize,
+) -> Vec<Line<'a>> {
+    let mut block = Vec::new();
+
+    while let Some((_, raw)) = lines.next_if(|(line_depth, _)| *line_depth == depth) {
+        block.push(Line {
+            content: raw.content,
+            children: nest(lines, depth + 1),
+            blank_lines: raw.blank_lines,
+            block: raw.block,
+        });
+    }
+
+    block
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn lines(i: &str) -> Vec<Line<'_>> {
+        let (_, (lines, errors)) = take_lines(Span::new_extra(i, "")).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        lines
+    }
+
+    fn errors(i: &str) -> Vec<(u32, ParserErrorKind<Span<'_>>)> {
+        let (_, (_, errors)) = take_lines(Span::new_extra(i, "")).unwrap();
+        errors
+            .into_iter()
+            .map(|e| (e.pos.location_line(), e.kind))
+            .collect()
+    }
+
     #[test]
     fn test_take_lines() {
         assert!(take_lines(Span::new_extra("", "")).is_ok());
         assert!(take_lines(Span::new_extra("\n", "")).is_ok());
 
-        assert_eq!(take_lines(Span::new_extra("\na\n", "")).unwrap().1.len(), 1);
-        assert_eq!(take_lines(Span::new_extra("a\n", "")).unwrap().1.len(), 1);
+        assert_eq!(lines("\na\n").len(), 1);
+        assert_eq!(lines("a\n").len(), 1);
+        assert_eq!(lines("a\n  b\n    ")[0].children.len(), 1);
+        assert_eq!(lines("a\n\tb\n\t\tc\n\td\n")[0].children.len(), 2);
+
+        // blank lines are counted, and don't end a block
+        let lines = lines("a\n  b\n\n  c\n\n\nd\n");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].children.len(), 2);
+        assert_eq!(lines[0].children[1].blank_lines, 1);
+        assert_eq!(lines[1].blank_lines, 2);
         // assert_eq!(take_lines("a\nb").unwrap().0.to_string(), "");
         // assert_eq!(take_lines("a\nb").unwrap().1[0].content.to_string(), "a");
         // assert_eq!(take_lines("a\nb").unwrap().1[1].content.to_string(), "b");
@@ -69,45 +212,298 @@ mod test {
         //     "c"
         // );
     }
-}
 
-fn take_children(i: Span) -> IResult<Span, Line> {
-    let (mut r, (indent, line)) = line(i)?;
-    let mut children: Vec<Line> = Vec::new();
+    #[test]
+    fn test_continuations() {
+        let source = "%link {\n  rel: \"a\",\n  href: \"b\",\n} text (see\nlet a = f(\n  x: [1,\n\n    2]\n)\nlet b = \\\n  1\n| text (\n  | ${ a(\n    ) }\n%p text \\\n  %p b\n";
+        let statements = lines(source);
+        let contents: Vec<&str> = statements.iter().map(|l| *l.content.fragment()).collect();
+        assert_eq!(
+            contents,
+            vec![
+                "%link {\n  rel: \"a\",\n  href: \"b\",\n} text (see",
+                "let a = f(\n  x: [1,\n\n    2]\n)",
+                "let b = \\\n  1",
+                "| text (",
+                "%p text \\",
+            ]
+        );
+        assert_eq!(
+            statements[3].children[0].content.fragment(),
+            &"| ${ a(\n    ) }"
+        );
+        assert_eq!(statements[4].children.len(), 1);
+
+        // text which starts with an expression
+        assert_eq!(
+            lines("%p ${ load(\n    path: ./a.md) } text (\n%p\n")[0]
+                .content
+                .fragment(),
+            &"%p ${ load(\n    path: ./a.md) } text ("
+        );
+
+        // an unclosed bracket only carries on over lines indented under it
+        assert_eq!(lines("%a { href: x\n%b\n").len(), 2);
+        assert_eq!(lines("%a\n  %b { c: \"{\"\n  %d\n")[0].children.len(), 2);
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let statements = lines("%a\n  --[ %b\n%c\n      %d ]--\n  %e\n%f\n");
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            statements[0].children[0].content.fragment(),
+            &"--[ %b\n%c\n      %d ]--"
+        );
+        assert_eq!(statements[0].children[1].content.fragment(), &"%e");
+
+        // unclosed, it takes everything after it
+        assert_eq!(lines("%a\n--[ b\n%c\n").len(), 2);
+    }
+
+    #[test]
+    fn test_text_blocks() {
+        let source = "%script :raw\n  if (a) {\n    b(\"${c}\");\n\n  }\n|||\n\t\tindented\n\n%p\n  |||\n  %p\n";
+        let statements = lines(source);
+        assert_eq!(statements.len(), 3);
+
+        let script: Vec<&str> = statements[0].block.iter().map(|l| *l.fragment()).collect();
+        assert_eq!(script, vec!["if (a) {", "  b(\"${c}\");", "", "}"]);
+        assert_eq!(statements[0].block[1].location_line(), 3);
+        assert_eq!(statements[1].block[0].fragment(), &"indented");
+
+        // `:raw` at the end of an element's text is just text
+        let p = lines("%p set the flag to :raw\n  %b bold\n");
+        assert!(p[0].block.is_empty());
+        assert_eq!(p[0].children.len(), 1);
+
+        // a block with nothing indented under it is empty, and doesn't take the next line
+        assert_eq!(statements[2].blank_lines, 1);
+        assert!(statements[2].children[0].block.is_empty());
+        assert_eq!(statements[2].children.len(), 2);
+    }
+
+    #[test]
+    fn test_indentation_errors() {
+        let mixed = ParserErrorKind::InconsistentIndentation {
+            unit: String::from("a tab"),
+            found: String::from("2 spaces"),
+        };
+        assert_eq!(errors("a\n\tb\n  c\n"), vec![(3, mixed)]);
+
+        let uneven = ParserErrorKind::InconsistentIndentation {
+            unit: String::from("2 spaces"),
+            found: String::from("3 spaces"),
+        };
+        assert_eq!(errors("a\n  b\n   c\n"), vec![(3, uneven)]);
+
+        assert_eq!(
+            errors("a\n  b\nc\n      d\n  e\n"),
+            vec![(4, ParserErrorKind::OverIndented)]
+        );
+        assert_eq!(errors("  a\n"), vec![(1, ParserErrorKind::OverIndented)]);
 
-    // see map_while
-    while line_indent(r.fragment()) > indent {
-        let (rem, child) = take_children(r)?;
-        children.push(child);
-        r = rem;
+        // lines indented under a rejected line go with it
+        let (_, (lines, errors)) =
+            take_lines(Span::new_extra("a\n  b\n     c\n       d\n  e\n", "")).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(lines[0].children.len(), 2);
+        assert_eq!(lines[0].children[1].content.fragment(), &"e");
     }
+}
+
+/// take a single statement, with the blank lines before it counted and thrown away, and chomp
+/// newline. statements are usually a line long, see `statement_length`.
+fn line(i: Span) -> IResult<Span, RawLine> {
+    let (i, blank_lines) = many0_count(tuple((space0, newline)))(i)?;
+    let (i, indent) = recognize(many0_count(one_of(" \t")))(i)?;
+
+    let (i, content) = match statement_length(i.fragment(), indent.fragment().len()) {
+        0 => return Err(Err::Error(Error::new(i, ErrorKind::IsNot))),
+        length => i.take_split(length),
+    };
+    let (i, _) = opt(newline)(i)?;
+
+    let (i, block) = match opens_block(content) {
+        true => i.take_split(block_length(i.fragment(), indent.fragment().len())),
+        false => i.take_split(0),
+    };
 
     Ok((
-        r,
-        Line {
-            content: line,
-            children,
+        i,
+        RawLine {
+            blank_lines,
+            indent,
+            content,
+            block: block_lines(block),
         },
     ))
 }
 
-/// take a single line in the format (indent, content) and chomp newline
-fn line(i: Span) -> IResult<Span, (usize, Span)> {
-    tuple((
-        opt(many0(tuple((space0, newline)))), // throw away blank lines
-        nom::multi::many0_count(one_of(" \t")),
-        is_not("\n"),
-        opt(newline),
-    ))(i)
-    .map(|(r, (_, indent, line, _))| (r, (indent, line)))
+/// whether the lines indented under a statement are text rather than statements: `|||`, or an
+/// element marked `:raw`
+fn opens_block(content: Span) -> bool {
+    let source = content.fragment();
+    match source.starts_with('%') && source.trim_end().ends_with(":raw") {
+        true => matches!(element(content), Ok((_, Element { block: Some(_), .. }))),
+        false => source.starts_with("|||"),
+    }
+}
+
+/// how many bytes of `i` the lines indented further than `indent` take up, along with any blank
+/// lines between them (but not after them) and the newline after the last one
+fn block_length(i: &str, indent: usize) -> usize {
+    let mut length = 0;
+    let mut position = 0;
+
+    for line in i.split_inclusive('\n') {
+        position += line.len();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.len() - line.trim_start_matches([' ', '\t']).len() <= indent {
+            break;
+        }
+        length = position;
+    }
+
+    length
+}
+
+/// each line of a block, without newlines or the indentation every line of it shares. blank lines
+/// are empty.
+fn block_lines(block: Span) -> Vec<Span> {
+    let lines: Vec<&str> = block.fragment().split_inclusive('\n').collect();
+    let shared_indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+
+    let mut offset = 0;
+    lines
+        .into_iter()
+        .map(|line| {
+            let start = offset;
+            let end = start + line.trim_end_matches(['\n', '\r']).len();
+            offset += line.len();
+
+            match line.trim().is_empty() {
+                true => block.slice(start..start),
+                false => block.slice(start + shared_indent..end),
+            }
+        })
+        .collect()
 }
 
-/// returns the position of the first non-whitespace character,
-/// or None if the line is entirely whitespace.
-fn indentation_level(i: &str) -> IResult<&str, usize> {
-    nom::multi::many0_count(one_of(" \t"))(i)
+#[derive(PartialEq)]
+enum Scan {
+    /// expressions, where brackets can be left open at the end of a line
+    Code,
+    /// an element's name, and then the space after it, either of which might be followed by
+    /// braced attributes
+    ElementName,
+    ElementSpace,
+    /// text after `|` or an element, where only `${` starts code
+    Text,
+}
+
+/// how many bytes of `i` the statement at its start takes up: the rest of the line, or more if
+/// the line ends inside brackets or with a `\` (in code, not text or comments). the statement is
+/// indented by `indent`, and only carries on over lines indented further (or level with it, to
+/// close a bracket), so a missing bracket can't swallow the rest of the document.
+fn statement_length(i: &str, indent: usize) -> usize {
+    if i.starts_with("--[") {
+        // to the end of the line the comment is closed on, or everything if it isn't
+        return match i.find("]--") {
+            Some(close) => i[close..].find('\n').map_or(i.len(), |n| close + n),
+            None => i.len(),
+        };
+    }
+
+    if i.starts_with("--") {
+        return i.find('\n').unwrap_or(i.len());
+    }
+
+    let mut scan = match i.chars().next() {
+        Some('|') => Scan::Text,
+        Some('%') => Scan::ElementName,
+        _ => Scan::Code,
+    };
+    // whether code goes back to being text once its brackets are closed
+    let in_text = scan != Scan::Code;
+    let mut brackets: usize = 0;
+    let mut in_string = false;
+    let mut previous = ' ';
+    let mut chars = i.char_indices().peekable();
+
+    while let Some((n, c)) = chars.next() {
+        if c == '\n' {
+            let continued = brackets > 0 || (previous == '\\' && scan != Scan::Text);
+            if !continued || !continues_onto(&i[n + 1..], indent) {
+                return n;
+            }
+            // strings don't go over lines
+            in_string = false;
+        }
+
+        match scan {
+            Scan::ElementName | Scan::ElementSpace => match c {
+                '{' => {
+                    brackets += 1;
+                    scan = Scan::Code;
+                }
+                c if c.is_whitespace() || c == '\\' => scan = Scan::ElementSpace,
+                // text which starts with an expression
+                '$' if scan == Scan::ElementSpace
+                    && chars.peek().map(|(_, next)| *next) == Some('{') =>
+                {
+                    chars.next();
+                    brackets += 1;
+                    scan = Scan::Code;
+                }
+                c if scan == Scan::ElementName && (c.is_alphanumeric() || c == '%') => {}
+                _ => scan = Scan::Text,
+            },
+            Scan::Text => {
+                if c == '$' && chars.peek().map(|(_, next)| *next) == Some('{') {
+                    chars.next();
+                    brackets += 1;
+                    scan = Scan::Code;
+                }
+            }
+            Scan::Code if in_string => in_string = c != '"',
+            Scan::Code => match c {
+                '"' => in_string = true,
+                '{' | '(' | '[' => brackets += 1,
+                '}' | ')' | ']' => {
+                    brackets = brackets.saturating_sub(1);
+                    if brackets == 0 && in_text {
+                        scan = Scan::Text;
+                    }
+                }
+                _ => {}
+            },
+        }
+
+        if c != '\r' {
+            previous = c;
+        }
+    }
+
+    i.len()
 }
 
-fn line_indent(i: &str) -> usize {
-    indentation_level(i).map(|(_r, indent)| indent).unwrap_or(0)
+/// whether the next line with anything on it can carry on a statement indented by `indent`
+fn continues_onto(rest: &str, indent: usize) -> bool {
+    match rest.lines().find(|line| !line.trim().is_empty()) {
+        Some(line) => {
+            let content = line.trim_start_matches([' ', '\t']);
+            let width = line.len() - content.len();
+
+            width > indent || (width == indent && content.starts_with(['}', ')', ']']))
+        }
+        None => false,
+    }
 }
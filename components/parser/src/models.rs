@@ -1,4 +1,5 @@
 use crate::Span;
+use rctree::Node;
 use std::fmt::Display;
 
 // #[derive(Debug, Clone, PartialEq)]
@@ -18,25 +19,121 @@ pub enum Statement<'a> {
     Route(Route<'a>),
     Element(Element<'a>),
     Text(Vec<StringToken<'a>>), // todo: replace with interpolatedstring
+    TextBlock(TextBlock<'a>),
     Comment(Span<'a>),
+    /// `--[ ... ]--`, which can be over several lines
+    BlockComment(Span<'a>),
+    /// `---`, documenting the `let` after it (see `docs`)
+    DocComment(Span<'a>),
     ForLoop {
         ident: Span<'a>,
         expr: Expression<'a>,
     },
 }
 
-impl Statement<'_> {
+impl<'a> Statement<'a> {
+    /// the statement as source, as `astryx fmt` writes it (the first line of it, for statements
+    /// with a text block)
     pub fn inspect(&self) -> String {
         match self {
             Statement::Expression(e) => e.inspect(),
-            Statement::Binding(_, _) => unimplemented!(),
-            Statement::Element(_) => unimplemented!(),
-            Statement::Text(_) => unimplemented!(),
-            Statement::Comment(_) => unimplemented!(),
-            Statement::ForLoop { ident: _, expr: _ } => unimplemented!(),
-            Statement::Route(_) => unimplemented!(),
+            Statement::Binding(ident, e) => format!("let {} = {}", ident, e.inspect()),
+            Statement::Element(e) => format!(
+                "%{}{}{}",
+                e.ident,
+                inspect_braced_attributes(&e.attributes),
+                e.text
+                    .as_ref()
+                    .filter(|t| !t.is_empty())
+                    .map(|t| format!(" {}", inspect_tokens(t)))
+                    .unwrap_or_default()
+                    + if e.block.is_some() { " :raw" } else { "" }
+            ),
+            Statement::Text(t) => format!("| {}", inspect_tokens(t)),
+            Statement::TextBlock(b) if b.interpolated => String::from("|||"),
+            Statement::TextBlock(_) => String::from("||| :raw"),
+            Statement::Comment(c) => format!("--{}", c.trim_end()),
+            Statement::BlockComment(c) => format!("--[{}]--", c),
+            Statement::DocComment(c) => format!("---{}", c.trim_end()),
+            Statement::ForLoop { ident, expr } => format!("for {} in {}", ident, expr.inspect()),
+            Statement::Route(r) => format!("@{}{}", r.ident, inspect_attributes(&r.attributes)),
         }
     }
+
+    /// the block of text under `|||` or an element marked `:raw`
+    pub fn text_block(&self) -> Option<&TextBlock<'a>> {
+        match self {
+            Statement::TextBlock(block) => Some(block),
+            Statement::Element(Element {
+                block: Some(block), ..
+            }) => Some(block),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn text_block_mut(&mut self) -> Option<&mut TextBlock<'a>> {
+        match self {
+            Statement::TextBlock(block) => Some(block),
+            Statement::Element(Element {
+                block: Some(block), ..
+            }) => Some(block),
+            _ => None,
+        }
+    }
+}
+
+fn inspect_attributes(attributes: &[(Span, Expression)]) -> String {
+    attributes
+        .iter()
+        .map(|(k, v)| format!(" {}={}", k, v.inspect()))
+        .collect()
+}
+
+/// ` { a: 1, b: 2 }`, or nothing when there aren't any
+fn inspect_braced_attributes(attributes: &[(Span, Expression)]) -> String {
+    if attributes.is_empty() {
+        return String::new();
+    }
+
+    format!(
+        " {{ {} }}",
+        attributes
+            .iter()
+            .map(|(k, v)| format!("{}: {}", k, v.inspect()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+pub(crate) fn inspect_tokens(tokens: &[StringToken]) -> String {
+    tokens
+        .iter()
+        .map(|t| match t {
+            StringToken::Text(s) => s.to_string(),
+            StringToken::Expression(e) => format!("${{{}}}", e.inspect()),
+        })
+        .collect()
+}
+
+/// a statement as it was written, with the blank lines above it and the lines indented under
+/// it. unlike the tree from `run`, this is enough to write the document back out.
+#[derive(Debug, Clone)]
+pub struct SourceLine<'a> {
+    pub statement: Statement<'a>,
+    pub blank_lines: usize,
+    pub children: Vec<SourceLine<'a>>,
+}
+
+impl<'a> SourceLine<'a> {
+    pub fn into_node(self) -> Node<Statement<'a>> {
+        let mut node = Node::new(self.statement);
+
+        for child in self.children {
+            node.append(child.into_node());
+        }
+
+        node
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,7 +155,13 @@ impl Expression<'_> {
             Expression::RelativePath(p) => p.to_string(),
             Expression::Reference(span) => span.fragment().to_string(),
             Expression::Literal(l) => l.inspect(),
-            Expression::Array(_) => unimplemented!(),
+            Expression::Array(a) => format!(
+                "[{}]",
+                a.iter()
+                    .map(Expression::inspect)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
             Expression::Index(i, e) => format!("{}.{}", i.inspect(), e.inspect()),
         }
     }
@@ -97,6 +200,7 @@ impl FunctionCall<'_> {
 pub enum Literal<'a> {
     String(Span<'a>),
     Number(Span<'a>, f64),
+    Boolean(Span<'a>, bool),
 }
 
 // impl Literal {
@@ -119,7 +223,7 @@ pub enum Literal<'a> {
 
 impl<'a> Display for Literal<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{}", self.inspect())
     }
 }
 
@@ -137,6 +241,18 @@ pub struct Element<'a> {
     pub ident: Span<'a>,
     pub attributes: Vec<(Span<'a>, Expression<'a>)>,
     pub text: Option<Vec<StringToken<'a>>>,
+    /// the lines indented under an element marked `:raw`, e.g. `%script :raw`
+    pub block: Option<TextBlock<'a>>,
+}
+
+/// lines of text under `|||` (or an element marked `:raw`), taken as they're written rather than
+/// as statements
+#[derive(Debug, Clone)]
+pub struct TextBlock<'a> {
+    /// each line, without the block's indentation
+    pub lines: Vec<Vec<StringToken<'a>>>,
+    /// whether `${}` is evaluated, which it isn't in `:raw` blocks
+    pub interpolated: bool,
 }
 
 #[derive(Debug, Clone)]
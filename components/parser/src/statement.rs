@@ -3,18 +3,20 @@ use crate::{
     error::ParserErrorKind,
     function::function_call,
     models::Statement,
-    text::piped_string,
+    text::{piped_string, text_block},
     variable::{glob_pattern, literal, relative_path},
+    whitespace::{list_end, ws0},
     Expression, ParserError, Route, Span,
 };
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric1, char, multispace0, space0, space1},
-    combinator::{all_consuming, cut, map},
-    multi::many0,
-    sequence::{terminated, tuple},
-    IResult,
+    bytes::complete::{tag, take_until},
+    character::complete::{alpha1, alphanumeric1, char, multispace0, space1},
+    combinator::{all_consuming, cut, map, not, rest},
+    error::{ErrorKind, FromExternalError, ParseError},
+    multi::{many0, separated_list0},
+    sequence::{delimited, preceded, terminated, tuple},
+    Err, IResult,
 };
 
 // fn array<'a>(i: Span) -> IResult<Span, Span, ParserError<Span>> {
@@ -38,12 +40,13 @@ use nom::{
 pub(crate) fn statement<'a>(i: Span<'a>) -> IResult<Span, Statement<'a>, ParserError<Span<'a>>> {
     all_consuming(alt((
         // map(function_call, |f| Statement::FunctionCall(f)),
-        map(comment, |s| Statement::Comment(s)),
+        comment,
         map(for_loop, |(ident, expr)| Statement::ForLoop { ident, expr }),
         map(binding, |(ident, expr)| Statement::Binding(ident, expr)),
         map(expression, |e| Statement::Expression(e)),
         map(route, |r| Statement::Route(r)),
         map(element, |e| Statement::Element(e)),
+        map(text_block, Statement::TextBlock),
         map(piped_string, |e| Statement::Text(e)),
         // map(alpha1, |e| Statement::Element(e)),
         // return_statement
@@ -76,7 +79,7 @@ pub struct ForLoop<'a> {
 }
 
 fn route<'a>(i: Span<'a>) -> IResult<Span<'a>, Route<'a>, ParserError<Span<'a>>> {
-    tuple((tag("@"), alphanumeric1, space0, many0(attribute_assignment)))(i)
+    tuple((tag("@"), alphanumeric1, ws0, many0(attribute_assignment)))(i)
         .map(|(r, (_, ident, _, attributes))| (r, Route { ident, attributes }))
         .map_err(|e: nom::Err<_>| {
             e.map(|e: ParserError<Span<'a>>| ParserError {
@@ -92,10 +95,10 @@ fn attribute_assignment<'a>(
     i: Span<'a>,
 ) -> IResult<Span<'a>, (Span<'a>, Expression), ParserError<Span<'a>>> {
     nom::sequence::tuple((
-        multispace0,
+        ws0,
         alpha1,
-        terminated(multispace0, char('=')),
-        space0,
+        terminated(ws0, char('=')),
+        ws0,
         cut(expression),
     ))(i)
     .map(|(r, (_, ident, _, _, value))| (r, (ident, value)))
@@ -110,6 +113,15 @@ pub(crate) fn expression<'a>(i: Span<'a>) -> IResult<Span, Expression<'a>, Parse
         map(glob_pattern, |s| Expression::GlobPattern(s)),
         map(function_call, |f| Expression::FunctionCall(f)),
         map(literal, |v| Expression::Literal(v)),
+        // arrays, which can be spread over several lines
+        map(
+            delimited(
+                tuple((char('['), ws0)),
+                separated_list0(tuple((ws0, char(','), ws0)), expression),
+                cut(list_end(']')),
+            ),
+            Expression::Array,
+        ),
         map(alphanumeric1, |s| Expression::Reference(s)),
     ))(i)
 }
@@ -140,15 +152,35 @@ fn index_expression<'a>(i: Span<'a>) -> IResult<Span, Expression<'a>, ParserErro
     })
 }
 
-fn comment<'a>(i: Span<'a>) -> IResult<Span<'a>, Span<'a>, ParserError<Span<'a>>> {
-    tag("--")(i).map(|(r, _)| (Span::new_extra("", ""), r)) // FXIME
-                                                            // .map_err(|e| {
-                                                            //     e.map(|(span, _kind)| ParserError {
-                                                            //         context: span,
-                                                            //         kind: ParserErrorKind::SyntaxError,
-                                                            //         pos: span.into(),
-                                                            //     })
-                                                            // })
+/// `-- a comment`, `--- documentation` for the `let` after it, or `--[ a comment ]--` which can
+/// go over any number of lines (see `linesplit`)
+fn comment<'a, E>(i: Span<'a>) -> IResult<Span<'a>, Statement<'a>, E>
+where
+    E: ParseError<Span<'a>> + FromExternalError<Span<'a>, ParserErrorKind<Span<'a>>>,
+{
+    alt((
+        map(
+            preceded(terminated(tag("---"), not(char('-'))), rest),
+            Statement::DocComment,
+        ),
+        map(
+            delimited(
+                tag("--["),
+                cut(take_until("]--")),
+                tuple((tag("]--"), multispace0)),
+            ),
+            Statement::BlockComment,
+        ),
+        map(preceded(tag("--"), rest), Statement::Comment),
+    ))(i)
+    .map_err(|e| match e {
+        Err::Failure(_) => Err::Failure(E::from_external_error(
+            i,
+            ErrorKind::TakeUntil,
+            ParserErrorKind::UnclosedComment,
+        )),
+        e => e,
+    })
 }
 
 fn binding<'a>(i: Span<'a>) -> IResult<Span, (Span<'a>, Expression<'a>), ParserError<Span<'a>>> {
@@ -156,8 +188,8 @@ fn binding<'a>(i: Span<'a>) -> IResult<Span, (Span<'a>, Expression<'a>), ParserE
         tag("let"),
         space1,
         alphanumeric1,
-        terminated(space0, tag("=")),
-        space0,
+        terminated(ws0, tag("=")),
+        ws0,
         expression,
     ))(i)
     .map(|(r, (_, _, ident, _, _, expr))| (r, (ident, expr)))
@@ -185,6 +217,26 @@ mod test {
         assert!(index(Span::new_extra("test.log()", "")).is_ok());
     }
 
+    #[test]
+    fn test_array() {
+        assert_eq!(
+            expression(Span::new_extra("[1, \"a\", b]", ""))
+                .unwrap()
+                .1
+                .inspect(),
+            "[1, \"a\", b]"
+        );
+        assert_eq!(
+            expression(Span::new_extra("[\n  1,\n  2,\n]", ""))
+                .unwrap()
+                .1
+                .inspect(),
+            "[1, 2]"
+        );
+        assert!(expression(Span::new_extra("[]", "")).is_ok());
+        assert!(expression(Span::new_extra("[1", "")).is_err());
+    }
+
     #[test]
     fn test_binding() {
         assert!(binding(Span::new_extra("let a=5", "")).is_ok());
@@ -211,6 +263,24 @@ mod test {
         assert!(route(Span::new_extra("@route a=5", "")).is_ok());
     }
 
+    #[test]
+    fn test_comment() {
+        let comment = |i| {
+            comment::<ParserError<Span>>(Span::new_extra(i, ""))
+                .map(|(_, c)| c.inspect())
+                .map_err(|e| e.map(|e| e.kind))
+        };
+
+        assert_eq!(comment("-- a comment").unwrap(), "-- a comment");
+        assert_eq!(comment("--- the title").unwrap(), "--- the title");
+        assert_eq!(comment("---------").unwrap(), "---------");
+        assert_eq!(comment("--[ a\n  b ]--\n").unwrap(), "--[ a\n  b ]--");
+        assert!(matches!(
+            comment("--[ a\n"),
+            Err(Err::Failure(ParserErrorKind::UnclosedComment))
+        ));
+    }
+
     #[test]
     fn test_statement() {
         assert!(statement(Span::new_extra("", "")).is_err()); // do not allow blank lines to slip through
@@ -1,10 +1,13 @@
-use crate::{statement::expression, Expression, ParserError, Span, StringToken};
+use crate::{statement::expression, Expression, ParserError, Span, StringToken, TextBlock};
 use nom::{
     branch::alt,
     bytes::complete::{is_not, tag},
-    character::complete::char,
     character::complete::multispace0,
-    combinator::map,
+    character::complete::{char, space0, space1},
+    combinator::{all_consuming, map, not, opt, recognize},
+    error::ParseError,
+    multi::many1,
+    sequence::{preceded, terminated, tuple},
     IResult,
 };
 
@@ -21,6 +24,46 @@ fn test_piped_string() {
     assert!(piped_string(Span::new_extra("| hi", "")).is_ok());
 }
 
+/// `|||`, or `||| :raw` to leave `${}` as it is. the block's lines are added by `linesplit`.
+pub(crate) fn text_block<'a, E: ParseError<Span<'a>>>(
+    i: Span<'a>,
+) -> IResult<Span<'a>, TextBlock<'a>, E> {
+    tuple((tag("|||"), opt(preceded(space1, tag(":raw"))), space0))(i).map(|(r, (_, raw, _))| {
+        (
+            r,
+            TextBlock {
+                lines: Vec::new(),
+                interpolated: raw.is_none(),
+            },
+        )
+    })
+}
+
+/// a line of a text block, as text and `${}` expressions or (in a `:raw` block) all text
+pub(crate) fn block_line<'a>(
+    line: Span<'a>,
+    interpolated: bool,
+    errors: &mut Vec<ParserError<Span<'a>>>,
+) -> Option<Vec<StringToken<'a>>> {
+    if line.fragment().is_empty() {
+        return Some(Vec::new());
+    }
+
+    if !interpolated {
+        return Some(vec![StringToken::Text(line)]);
+    }
+
+    match all_consuming(tokenised_string)(line) {
+        Ok((_, tokens)) => Some(tokens),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            errors.push(e);
+            None
+        }
+        // only streaming parsers are incomplete
+        Err(nom::Err::Incomplete(_)) => Some(vec![StringToken::Text(line)]),
+    }
+}
+
 pub(crate) fn tokenised_string<'a>(
     i: Span<'a>,
 ) -> IResult<Span<'a>, Vec<StringToken>, ParserError<Span<'a>>> {
@@ -32,22 +75,20 @@ pub(crate) fn tokenised_string<'a>(
     )))(i)
 }
 
+/// text up to the end of the line or the next `${`
 fn raw_text<'a>(i: Span<'a>) -> IResult<Span<'a>, Span<'a>, ParserError<Span<'a>>> {
-    is_not("\n")(i)
+    recognize(many1(alt((
+        is_not("$\n"),
+        terminated(tag("$"), not(char('{'))),
+    ))))(i)
 }
 
 fn interpolated_expression<'a>(
     i: Span<'a>,
 ) -> IResult<Span<'a>, Expression<'a>, ParserError<Span<'a>>> {
-    nom::sequence::tuple((
-        multispace0,
-        tag("${"),
-        multispace0,
-        expression,
-        multispace0,
-        char('}'),
-    ))(i)
-    .map(|(r, (_, _, _, var, _, _))| (r, var))
+    // whitespace before the `${` is text, so `${a} ${b}` keeps its space
+    nom::sequence::tuple((tag("${"), multispace0, expression, multispace0, char('}')))(i)
+        .map(|(r, (_, _, var, _, _))| (r, var))
     // .map_err(|e| {
     //     e.map(|(s, _k)| ParserError {
     //         context: i, // we need to reset the context to the whole line
@@ -65,4 +106,37 @@ mod test {
     fn test_interpolated_expression() {
         assert!(interpolated_expression(Span::new_extra("", "")).is_err());
     }
+
+    #[test]
+    fn test_text_block() {
+        let text_block = text_block::<ParserError<Span>>;
+        assert!(
+            text_block(Span::new_extra("|||", ""))
+                .unwrap()
+                .1
+                .interpolated
+        );
+        assert!(
+            !text_block(Span::new_extra("||| :raw", ""))
+                .unwrap()
+                .1
+                .interpolated
+        );
+
+        let mut errors = Vec::new();
+        let line = Span::new_extra("const a = `${b}`;", "");
+        assert_eq!(block_line(line, true, &mut errors).unwrap().len(), 3);
+        assert_eq!(block_line(line, false, &mut errors).unwrap().len(), 1);
+        assert!(block_line(Span::new_extra("${", ""), true, &mut errors).is_none());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_tokenised_string() {
+        let (_, tokens) = tokenised_string(Span::new_extra("${a} and ${b} cost $5", "")).unwrap();
+        assert_eq!(tokens.len(), 4);
+
+        let (_, tokens) = tokenised_string(Span::new_extra("${ a } ${b}", "")).unwrap();
+        assert_eq!(tokens.len(), 3);
+    }
 }
@@ -2,10 +2,11 @@ use crate::{error::ParserErrorKind, Literal, ParserError, Span};
 use nom::{
     branch::alt,
     bytes::complete::{is_not, tag},
-    character::complete::char,
-    combinator::map,
+    character::complete::{char, satisfy},
+    combinator::{consumed, map, not, recognize},
+    error::ParseError,
     number::complete::double,
-    sequence::{delimited, tuple},
+    sequence::{delimited, terminated, tuple},
     IResult,
 };
 
@@ -14,10 +15,11 @@ pub(crate) fn literal<'a>(i: Span<'a>) -> IResult<Span<'a>, Literal<'a>, ParserE
         // map(hash, JsonValue::Object),
         // map(array, JsonValue::Array),
         map(quoted_string, |s: Span| Literal::String(s)),
+        boolean,
         // map(relative_path, |s: Span| Variable::RelativePath(s)),
         // map(alphanumeric1, |s: Span| Variable::Reference(s)),
         // map(argument_idx,   |i| Property::ArgumentIndex(i.parse::<usize>().unwrap())),
-        map(double, |f| Literal::Number(i, f)),
+        map(consumed(double), |(s, f)| Literal::Number(s, f)),
         // map(digit1,         |i:&str| Property::Number(i.parse::<i64>().unwrap_or(0))),
         // map(boolean,        |b| Property::Boolean(b)),
         // map(dotted_symbol,  |s| Property::DottedSymbol(String::from(s))),
@@ -32,6 +34,20 @@ pub(crate) fn literal<'a>(i: Span<'a>) -> IResult<Span<'a>, Literal<'a>, ParserE
     // })
 }
 
+fn boolean<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> IResult<Span<'a>, Literal<'a>, E> {
+    alt((
+        map(keyword("true"), |s| Literal::Boolean(s, true)),
+        map(keyword("false"), |s| Literal::Boolean(s, false)),
+    ))(i)
+}
+
+/// match a whole word, so `trueish` and `true_x` aren't read as `true`
+fn keyword<'a, E: ParseError<Span<'a>>>(
+    word: &'static str,
+) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, Span<'a>, E> {
+    terminated(tag(word), not(satisfy(|c| c.is_alphanumeric() || c == '_')))
+}
+
 // fn number<'a>(i: Span<'a>) -> IResult<Span<'a>, Literal, ParserError<Span<'a>>> {
 //     let (r, f) = double(i)?;
 //     Ok((r, Literal::Number(i, f)))
@@ -43,16 +59,13 @@ fn quoted_string<'a>(i: Span<'a>) -> IResult<Span, Span, ParserError<Span<'a>>>
 
 /// match glob patterns eg: ./*.txt and ../../*
 pub fn glob_pattern<'a>(i: Span<'a>) -> IResult<Span<'a>, Span<'a>, ParserError<Span<'a>>> {
-    tuple((path_prefix, glob_pattern_characters))(i)
-        // .map(|(r, (prefix, pathname))| (r, Span::new(&format!("{}{}", prefix, pathname)))) // check this!
-        .map(|(r, (_prefix, path))| (r, path)) // fix this so that prefix is included
-        .map_err(|e| {
-            e.map(|_| ParserError {
-                kind: ParserErrorKind::Unexpected,
-                pos: i,
-                context: i,
-            })
+    recognize(tuple((path_prefix, glob_pattern_characters)))(i).map_err(|e| {
+        e.map(|_| ParserError {
+            kind: ParserErrorKind::Unexpected,
+            pos: i,
+            context: i,
         })
+    })
 }
 
 /// match relative paths eg: ./test.txt and ../../test.txt
@@ -66,16 +79,13 @@ pub fn relative_path<'a>(i: Span<'a>) -> IResult<Span<'a>, Span<'a>, ParserError
         }));
     };
 
-    tuple((path_prefix, path_characters))(i)
-        // .map(|(r, (prefix, pathname))| (r, Span::new(&format!("{}{}", prefix, pathname)))) // check this!
-        .map(|(r, (_prefix, path))| (r, path)) // fix this so that prefix is included
-        .map_err(|e| {
-            e.map(|_| ParserError {
-                context: i, // we need to reset the context to the whole line
-                kind: ParserErrorKind::UnexpectedToken("gg".into()),
-                pos: i,
-            })
+    recognize(tuple((path_prefix, path_characters)))(i).map_err(|e| {
+        e.map(|_| ParserError {
+            context: i, // we need to reset the context to the whole line
+            kind: ParserErrorKind::UnexpectedToken("gg".into()),
+            pos: i,
         })
+    })
 }
 
 fn glob_pattern_characters(i: Span) -> IResult<Span, Span> {
@@ -95,7 +105,9 @@ impl Literal<'_> {
     pub fn inspect(&self) -> String {
         match self {
             Literal::String(s) => format!("\"{}\"", s.fragment().to_string()),
-            Literal::Number(_, f) => f.to_string(),
+            // as written, so 1.50 stays 1.50
+            Literal::Number(s, _) => s.fragment().to_string(),
+            Literal::Boolean(_, b) => b.to_string(),
         }
     }
 }
@@ -110,5 +122,34 @@ mod test {
             literal(Span::new_extra("4", "")).unwrap().1.inspect(),
             String::from("4")
         );
+        assert_eq!(
+            literal(Span::new_extra("true", "")).unwrap().1.inspect(),
+            String::from("true")
+        );
+        assert!(literal(Span::new_extra("trueish", "")).is_err());
+        assert!(literal(Span::new_extra("true_x", "")).is_err());
+        assert!(literal(Span::new_extra("false)", "")).is_ok());
+        assert_eq!(
+            literal(Span::new_extra("1.50 ", "")).unwrap().1.inspect(),
+            String::from("1.50")
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(
+            relative_path(Span::new_extra("../a.md", ""))
+                .unwrap()
+                .1
+                .fragment(),
+            &"../a.md"
+        );
+        assert_eq!(
+            glob_pattern(Span::new_extra("./posts/*.md", ""))
+                .unwrap()
+                .1
+                .fragment(),
+            &"./posts/*.md"
+        );
     }
 }
@@ -1,14 +1,32 @@
 use crate::Span;
 use nom::{
-    bytes::complete::is_not,
-    character::complete::{newline, one_of, space0},
-    combinator::{cut, opt},
-    multi::many0,
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::{char, multispace1, newline, one_of, space0},
+    combinator::{cut, opt, recognize},
+    error::ParseError,
+    multi::{many0, many0_count},
     sequence::tuple,
     IResult,
 };
 use rctree::Node;
 
+/// whitespace between the tokens of a statement, including the line breaks of a statement
+/// continued over several lines (`linesplit` decides where a statement ends)
+pub(crate) fn ws0<'a, E: ParseError<Span<'a>>>(i: Span<'a>) -> IResult<Span<'a>, Span<'a>, E> {
+    recognize(many0_count(alt((multispace1, tag("\\\n"), tag("\\\r\n")))))(i)
+}
+
+/// the end of a bracketed list, which may have a trailing comma
+pub(crate) fn list_end<'a, E: ParseError<Span<'a>>>(
+    close: char,
+) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, char, E> {
+    move |i| {
+        tuple((opt(tuple((ws0, char(',')))), ws0, char(close)))(i)
+            .map(|(r, (_, _, close))| (r, close))
+    }
+}
+
 pub(crate) fn split<'a>(i: Span<'a>) -> IResult<Span<'a>, Vec<Node<Span<'a>>>> {
     let (r, lines) = cut(many0(line))(i)?;
     let mut current_indent = 0;
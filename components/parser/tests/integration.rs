@@ -23,6 +23,50 @@ fn test_parser() {
     assert_run("post.markdown()", "post.markdown()");
 }
 
+#[test]
+fn test_run_all() {
+    let (nodes, errors) = run_all("print()\n!\n%div\n  %p ok\n  ]\nlog()\n", "");
+    assert_eq!(nodes.len(), 3);
+    assert_eq!(nodes[1].children().count(), 1);
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| e.pos.location_line())
+            .collect::<Vec<u32>>(),
+        vec![2, 5]
+    );
+}
+
+#[test]
+fn test_multiline() {
+    let source = "%link {\n  rel: \"stylesheet\",\n  href: style,\n}\nlet a = f(\n  x: [1, 2],\n)\n@route \\\n  path=\"/\"\n";
+    let nodes = run(source, "").unwrap();
+
+    assert_eq!(
+        nodes
+            .iter()
+            .map(|n| n.borrow().inspect())
+            .collect::<Vec<String>>(),
+        vec![
+            "%link { rel: \"stylesheet\", href: style }",
+            "let a = f(x: [1, 2])",
+            "@route path=\"/\"",
+        ]
+    );
+
+    // spans still point at the lines they're on
+    let link = nodes[0].borrow();
+    match &*link {
+        Statement::Element(e) => match &e.attributes[1].1 {
+            Expression::Reference(s) => {
+                assert_eq!((s.location_line(), s.get_column()), (3, 9))
+            }
+            e => panic!("expected a reference, got {:?}", e),
+        },
+        s => panic!("expected an element, got {:?}", s),
+    }
+}
+
 #[test]
 fn test_error_position() {
     fn assert_error_position(i: &str, line: u32, column: usize) {
@@ -0,0 +1,207 @@
+// compact tinysearch-style search index
+//
+// every document gets its own bloom filter over its (stemmed) terms. the filters are
+// serialised into a single binary blob which `search.js` fetches and queries in the
+// browser without any wasm, so the index stays a fraction of the size of elasticlunr json.
+//
+// blob layout (all integers little endian):
+//   magic b"ASTS", version u8, document count u32
+//   per document: title len u16, title, url len u16, url, bit count u32, hash count u8, bits
+
+use rust_stemmers::{Algorithm, Stemmer};
+
+pub const SEARCH_JS: &str = include_str!("search.js");
+
+const MAGIC: &[u8; 4] = b"ASTS";
+const VERSION: u8 = 1;
+
+/// target false positive rate for each document filter
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+pub struct BloomIndexBuilder {
+    documents: Vec<Document>,
+    stemmer: Stemmer,
+}
+
+struct Document {
+    title: String,
+    url: String,
+    filter: BloomFilter,
+}
+
+impl Default for BloomIndexBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BloomIndexBuilder {
+    pub fn new() -> Self {
+        BloomIndexBuilder {
+            documents: Vec::new(),
+            stemmer: Stemmer::create(Algorithm::English),
+        }
+    }
+
+    pub fn insert(&mut self, title: &str, url: &str, body: &str) {
+        let mut terms: Vec<String> = Vec::new();
+
+        for word in tokenize(title).chain(tokenize(body)) {
+            // the js shim cannot stem, so both forms go in the filter and
+            // the shim strips common suffixes from queries instead.
+            let stem = self.stemmer.stem(&word).to_string();
+            if stem != word {
+                terms.push(stem);
+            }
+            terms.push(word);
+        }
+
+        terms.sort();
+        terms.dedup();
+
+        let mut filter = BloomFilter::with_capacity(terms.len(), FALSE_POSITIVE_RATE);
+        for term in &terms {
+            filter.insert(term);
+        }
+
+        self.documents.push(Document {
+            title: title.into(),
+            url: url.into(),
+            filter,
+        });
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.documents.len() as u32).to_le_bytes());
+
+        for document in &self.documents {
+            write_string(&mut bytes, &document.title);
+            write_string(&mut bytes, &document.url);
+            bytes.extend_from_slice(&(document.filter.bit_count as u32).to_le_bytes());
+            bytes.push(document.filter.hash_count);
+            bytes.extend_from_slice(&document.filter.bits);
+        }
+
+        bytes
+    }
+}
+
+/// strings are truncated at a char boundary to fit their u16 length prefix
+fn write_string(bytes: &mut Vec<u8>, s: &str) {
+    let mut end = s.len().min(u16::MAX as usize);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    bytes.extend_from_slice(&(end as u16).to_le_bytes());
+    bytes.extend_from_slice(&s.as_bytes()[..end]);
+}
+
+/// lowercased alphanumeric words, must match `tokenize` in search.js
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(|word| word.to_lowercase())
+}
+
+struct BloomFilter {
+    bits: Vec<u8>,
+    bit_count: usize,
+    hash_count: u8,
+}
+
+impl BloomFilter {
+    fn with_capacity(items: usize, false_positive_rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+
+        // round up to whole bytes, it costs nothing extra in the blob
+        let bit_count = (-(items * false_positive_rate.ln()) / (ln2 * ln2)).ceil() as usize;
+        let bit_count = bit_count.max(8).div_ceil(8) * 8;
+        let hash_count = ((bit_count as f64 / items) * ln2).round().clamp(1.0, 16.0) as u8;
+
+        BloomFilter {
+            bits: vec![0; bit_count / 8],
+            bit_count,
+            hash_count,
+        }
+    }
+
+    fn insert(&mut self, term: &str) {
+        for bit in self.positions(term) {
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    #[cfg(test)]
+    fn contains(&self, term: &str) -> bool {
+        self.positions(term)
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// double hashing over two fnv-1a variants, mirrored in search.js
+    fn positions(&self, term: &str) -> impl Iterator<Item = usize> {
+        let h1 = fnv1a(term.as_bytes(), 0x811c_9dc5);
+        let h2 = fnv1a(term.as_bytes(), 0x0100_0193) | 1;
+        let bit_count = self.bit_count as u32;
+
+        (0..self.hash_count as u32)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bit_count) as usize)
+    }
+}
+
+fn fnv1a(bytes: &[u8], offset_basis: u32) -> u32 {
+    bytes.iter().fold(offset_basis, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::with_capacity(3, FALSE_POSITIVE_RATE);
+        filter.insert("static");
+        filter.insert("site");
+        filter.insert("generator");
+
+        assert!(filter.contains("static"));
+        assert!(filter.contains("generator"));
+        assert!(!filter.contains("elasticlunr"));
+    }
+
+    #[test]
+    fn test_stemmed_terms() {
+        let mut index = BloomIndexBuilder::new();
+        index.insert("Running Rust", "/running", "Builds are running quickly.");
+
+        let filter = &index.documents[0].filter;
+        assert!(filter.contains("running"));
+        assert!(filter.contains("run"));
+        assert!(filter.contains("build"));
+        assert!(filter.contains("quick"));
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let mut index = BloomIndexBuilder::new();
+        index.insert("a title", "/a", "some body text");
+        index.insert("another title", "/b", "more body text");
+
+        let bytes = index.to_bytes();
+        assert_eq!(&bytes[0..4], MAGIC);
+        assert_eq!(bytes[4], VERSION);
+        assert_eq!(
+            u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]),
+            2
+        );
+        assert_eq!(&bytes[9..11], &7u16.to_le_bytes());
+        assert_eq!(&bytes[11..18], b"a title");
+    }
+}
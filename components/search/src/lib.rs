@@ -1,11 +1,117 @@
 use elasticlunr::{Index, Language};
 
+pub mod bloom;
+pub use bloom::BloomIndexBuilder;
+
 pub const ELASTICLUNR_JS: &str = include_str!("elasticlunr.min.js");
 
+/// search index formats, chosen per build
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// full elasticlunr json index with ranked results
+    Elasticlunr,
+    /// compact per-document bloom filters (see `bloom`)
+    Bloom,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elasticlunr" => Ok(Backend::Elasticlunr),
+            "bloom" | "tinysearch" => Ok(Backend::Bloom),
+            _ => Err(format!("unknown search backend: {}", s)),
+        }
+    }
+}
+
+pub enum SearchIndex {
+    Elasticlunr(IndexBuilder),
+    Bloom(BloomIndexBuilder),
+}
+
+impl SearchIndex {
+    pub fn new(backend: Backend) -> Self {
+        match backend {
+            Backend::Elasticlunr => SearchIndex::Elasticlunr(IndexBuilder::new()),
+            Backend::Bloom => SearchIndex::Bloom(BloomIndexBuilder::new()),
+        }
+    }
+
+    pub fn insert(&mut self, title: &str, url: &str, body: &str) {
+        match self {
+            SearchIndex::Elasticlunr(index) => index.insert(url, &[title, body]),
+            SearchIndex::Bloom(index) => index.insert(title, url, body),
+        }
+    }
+
+    /// index a rendered page, titled by its `<title>` or else its url
+    pub fn insert_page(&mut self, url: &str, html: &str) {
+        let (title, body) = page_text(html);
+        self.insert(title.as_deref().unwrap_or(url), url, &body);
+    }
+
+    /// the (filename, content) pairs to write alongside the site
+    pub fn files(&self) -> Vec<(&'static str, Vec<u8>)> {
+        match self {
+            SearchIndex::Elasticlunr(index) => vec![
+                ("search_index.json", index.to_json().into_bytes()),
+                ("elasticlunr.min.js", ELASTICLUNR_JS.as_bytes().to_vec()),
+            ],
+            SearchIndex::Bloom(index) => vec![
+                ("search_index.bin", index.to_bytes()),
+                ("search.js", bloom::SEARCH_JS.as_bytes().to_vec()),
+            ],
+        }
+    }
+}
+
+/// the text of a page's `<title>`, if it has one, and the text of its `<body>` (or the
+/// whole page without one)
+fn page_text(html: &str) -> (Option<String>, String) {
+    // from the start of `open`, which is stripped along with every other tag
+    let element = |open: &str, close: &str| {
+        let start = html.find(open)?;
+        let end = html[start..].find(close)? + start;
+        Some(&html[start..end])
+    };
+
+    let title = element("<title>", "</title>").map(strip_tags);
+    let body = strip_tags(element("<body", "</body>").unwrap_or(html));
+    (title, body)
+}
+
+/// text without its tags, which are replaced by spaces so words either side stay apart
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 pub struct IndexBuilder {
     index: Index
 }
 
+impl Default for IndexBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IndexBuilder {
     pub fn new() -> Self {
         IndexBuilder {
@@ -18,6 +124,24 @@ impl IndexBuilder {
     }
 
     pub fn to_json(&self) -> String {
-        String::new()
+        self.index.to_json()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_page_text() {
+        let (title, body) = page_text(
+            "<html><head><title>A <em>Post</em></title></head><body class=\"x\"><h1>Hello</h1><p>some<br>text</p></body></html>",
+        );
+        assert_eq!(title.as_deref(), Some("A Post"));
+        assert_eq!(body, "Hello some text");
+
+        let (title, body) = page_text("<p>no page</p>");
+        assert_eq!(title, None);
+        assert_eq!(body, "no page");
     }
 }
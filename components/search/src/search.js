@@ -0,0 +1,98 @@
+// astryx bloom filter search, reads the blob written by search::bloom
+// usage: astryxSearch("/search_index.bin").then(function (search) { search("query") })
+(function (global) {
+  "use strict";
+
+  var SUFFIXES = ["ies", "es", "s", "ing", "ed", "ly", "er"];
+
+  function fnv1a(bytes, hash) {
+    for (var i = 0; i < bytes.length; i++) {
+      hash = Math.imul(hash ^ bytes[i], 0x01000193) >>> 0;
+    }
+    return hash;
+  }
+
+  function tokenize(text) {
+    return text
+      .toLowerCase()
+      .split(/[^\p{L}\p{N}]+/u)
+      .filter(function (word) { return Array.from(word).length > 1; });
+  }
+
+  // the index holds both raw words and their stems, so trying a few
+  // suffix-stripped forms of each query word is enough to hit a stem.
+  function variants(word) {
+    var forms = [word];
+    SUFFIXES.forEach(function (suffix) {
+      if (word.length > suffix.length + 2 && word.endsWith(suffix)) {
+        forms.push(word.slice(0, -suffix.length) + (suffix === "ies" ? "y" : ""));
+      }
+    });
+    return forms;
+  }
+
+  function contains(doc, bytes) {
+    var h1 = fnv1a(bytes, 0x811c9dc5);
+    var h2 = (fnv1a(bytes, 0x01000193) | 1) >>> 0;
+    for (var i = 0; i < doc.hashCount; i++) {
+      var bit = ((h1 + Math.imul(i, h2)) >>> 0) % doc.bitCount;
+      if ((doc.bits[bit >> 3] & (1 << (bit & 7))) === 0) return false;
+    }
+    return true;
+  }
+
+  function parse(buffer) {
+    var view = new DataView(buffer);
+    var decoder = new TextDecoder();
+    var magic = decoder.decode(new Uint8Array(buffer, 0, 4));
+    if (magic !== "ASTS" || view.getUint8(4) !== 1) throw new Error("invalid search index");
+
+    var offset = 9;
+    function string() {
+      var len = view.getUint16(offset, true);
+      var s = decoder.decode(new Uint8Array(buffer, offset + 2, len));
+      offset += 2 + len;
+      return s;
+    }
+
+    var docs = [];
+    for (var count = view.getUint32(5, true); count > 0; count--) {
+      var title = string();
+      var url = string();
+      var bitCount = view.getUint32(offset, true);
+      var hashCount = view.getUint8(offset + 4);
+      var bits = new Uint8Array(buffer, offset + 5, bitCount / 8);
+      offset += 5 + bitCount / 8;
+      docs.push({ title: title, url: url, bitCount: bitCount, hashCount: hashCount, bits: bits });
+    }
+    return docs;
+  }
+
+  function load(url) {
+    var encoder = new TextEncoder();
+
+    return fetch(url)
+      .then(function (response) { return response.arrayBuffer(); })
+      .then(function (buffer) {
+        var docs = parse(buffer);
+
+        // returns every document matching all query words as { title, url }
+        return function search(query) {
+          var words = tokenize(query).map(function (word) {
+            return variants(word).map(function (form) { return encoder.encode(form); });
+          });
+          if (words.length === 0) return [];
+
+          return docs
+            .filter(function (doc) {
+              return words.every(function (forms) {
+                return forms.some(function (bytes) { return contains(doc, bytes); });
+              });
+            })
+            .map(function (doc) { return { title: doc.title, url: doc.url }; });
+        };
+      });
+  }
+
+  global.astryxSearch = load;
+})(this);
@@ -1,14 +1,134 @@
-use error::{AstryxError, AstryxResult};
-use models::{Site, State};
-use std::cell::RefCell;
-use std::rc::Rc;
-
-pub(crate) fn build<'a>(file: &'a str, path: &str) -> AstryxResult<()> {
-    let state = Rc::new(RefCell::new(State::new()));
-
-    parser::run(file, path)
-        .map_err(AstryxError::from)
-        .and_then(|nodes| interpreter::run(&nodes, state))
-        .map(Site::render)
-        .map(|site| site.write())
+use crate::cache::BuildCache;
+use crate::config::Config;
+use error::AstryxResult;
+use models::Site;
+use search::{Backend, SearchIndex};
+use std::collections::HashSet;
+use std::path::Path;
+
+pub(crate) fn build(file: &str, path: &str, config: &Config, output: &Path) -> AstryxResult<()> {
+    // routes whose inputs are unchanged since the last build are skipped
+    let previous = BuildCache::load(output, config);
+    let fresh = previous.fresh_routes(output);
+
+    let (site, dependencies) = crate::render::render(file, path, config, &fresh)?;
+    site.write(output)?;
+
+    if !fresh.is_empty() {
+        eprintln!("skipped {} unchanged routes", fresh.len());
+    }
+
+    if let Some(backend) = config.search {
+        write_search_index(backend, &site, &fresh, output)?;
+    }
+
+    for dir in &config.static_dirs {
+        copy_dir(dir, output)?;
+    }
+
+    BuildCache::new(&dependencies, &previous).save(output, config)
+}
+
+/// index every page, reading those skipped as `fresh` back from `output`, and write the
+/// index's files into it
+fn write_search_index(
+    backend: Backend,
+    site: &Site,
+    fresh: &HashSet<String>,
+    output: &Path,
+) -> AstryxResult<()> {
+    let mut routes: Vec<&String> = site.documents.keys().chain(fresh).collect();
+    routes.sort();
+
+    let mut index = SearchIndex::new(backend);
+    for route in routes {
+        let page = match site.documents.get(route) {
+            Some(page) => page.clone(),
+            None => std::fs::read_to_string(Site::document_path(output, route))?,
+        };
+        index.insert_page(route, &page);
+    }
+
+    std::fs::create_dir_all(output)?;
+    for (name, content) in index.files() {
+        std::fs::write(output.join(name), content)?;
+    }
+
+    Ok(())
+}
+
+/// copy everything in `from` into `to`, leaving files which are already up to date
+fn copy_dir(from: &Path, to: &Path) -> AstryxResult<()> {
+    if !from.is_dir() {
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let source = entry.path();
+        let destination = to.join(entry.file_name());
+
+        if source.is_dir() {
+            copy_dir(&source, &destination)?;
+        } else if is_outdated(&source, &destination) {
+            std::fs::create_dir_all(to)?;
+            std::fs::copy(&source, &destination)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_outdated(source: &Path, destination: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    match (modified(source), modified(destination)) {
+        (Some(source), Some(destination)) => source > destination,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_search_index() {
+        let source = "@route path=\"/\"\n  %title home\n  %p hello\n@route path=\"/a\"\n  %p a\n";
+
+        for (backend, files) in [
+            (Backend::Bloom, ["search_index.bin", "search.js"]),
+            (
+                Backend::Elasticlunr,
+                ["search_index.json", "elasticlunr.min.js"],
+            ),
+        ] {
+            let output = std::env::temp_dir().join(format!(
+                "astryx-search-{:?}-{}",
+                backend,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&output);
+
+            let mut config = Config::default();
+            config.search = Some(backend);
+            config.static_dirs = Vec::new();
+
+            // the second build skips both routes, which are still indexed
+            for _ in 0..2 {
+                build(source, "site.astryx", &config, &output).unwrap();
+                for file in &files {
+                    assert!(output.join(file).exists(), "{:?} {}", backend, file);
+                }
+            }
+
+            if backend == Backend::Elasticlunr {
+                let index = std::fs::read_to_string(output.join(files[0])).unwrap();
+                assert!(index.contains("\"/a\""));
+                assert!(index.contains("hello"));
+            }
+
+            std::fs::remove_dir_all(&output).unwrap();
+        }
+    }
 }
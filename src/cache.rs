@@ -0,0 +1,294 @@
+// render caching for the dev server and incremental builds
+//
+// a rendered site is kept along with a fingerprint of every input the interpreter
+// touched. it's only rendered again when one of those inputs changes.
+//
+// `build` keeps the inputs of each route in a cache file in the output directory
+// instead, and only renders routes whose inputs changed since the last build with the
+// same config.
+
+use crate::config::Config;
+use error::AstryxResult;
+use models::{Dependencies, Site};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) const BUILD_CACHE_FILE: &str = ".astryx-cache";
+/// caches from other versions are ignored, as they may render differently
+const BUILD_CACHE_HEADER: &str = concat!("astryx-cache ", env!("CARGO_PKG_VERSION"));
+
+/// modification time and size of each input, `None` for missing files
+type Fingerprint = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+#[derive(Default)]
+pub(crate) struct RenderCache {
+    /// the environment from `astryx.toml`, which is read again on every render
+    env: Option<String>,
+    entry: Option<(Fingerprint, Site)>,
+}
+
+impl RenderCache {
+    pub(crate) fn new(env: Option<String>) -> Self {
+        RenderCache { env, entry: None }
+    }
+
+    /// the rendered site at `path`, rendering it again only if an input has changed
+    pub(crate) fn render(&mut self, path: &str) -> AstryxResult<&Site> {
+        match self.entry.take() {
+            Some((fingerprint, site)) if !is_stale(&fingerprint) => {
+                Ok(&self.entry.insert((fingerprint, site)).1)
+            }
+            // failed renders aren't cached, so they're retried on the next request
+            _ => {
+                let config = Config::load(self.env.as_deref())?;
+                let (site, inputs) = render(path, &config)?;
+                Ok(&self.entry.insert((fingerprint(&inputs), site)).1)
+            }
+        }
+    }
+}
+
+/// read, interpret and render the site at `path`, returning it with every file it read
+fn render(path: &str, config: &Config) -> AstryxResult<(Site, Vec<PathBuf>)> {
+    let (site, dependencies) =
+        crate::render::render(&read_to_string(path)?, path, config, &HashSet::new())?;
+
+    Ok((site, dependencies.inputs()))
+}
+
+fn fingerprint(inputs: &[PathBuf]) -> Fingerprint {
+    inputs
+        .iter()
+        .map(|path| (path.clone(), modified(path)))
+        .collect()
+}
+
+fn is_stale(fingerprint: &Fingerprint) -> bool {
+    fingerprint
+        .iter()
+        .any(|(path, previous)| modified(path) != *previous)
+}
+
+/// the inputs of the last build
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct BuildCache {
+    fingerprints: HashMap<PathBuf, Option<(SystemTime, u64)>>,
+    global: Vec<PathBuf>,
+    routes: HashMap<String, Vec<PathBuf>>,
+}
+
+impl BuildCache {
+    /// the cache from a previous build in `dir` with the same `config`, or an empty one
+    /// (a full build)
+    pub(crate) fn load(dir: &Path, config: &Config) -> Self {
+        read_to_string(dir.join(BUILD_CACHE_FILE))
+            .ok()
+            .and_then(|cache| Self::parse(&cache, &header(config)))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, dir: &Path, config: &Config) -> AstryxResult<()> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(BUILD_CACHE_FILE), self.serialize(&header(config)))?;
+        Ok(())
+    }
+
+    /// the inputs of this build. routes skipped because they were fresh keep their
+    /// inputs from `previous`.
+    pub(crate) fn new(dependencies: &Dependencies, previous: &BuildCache) -> Self {
+        let mut routes = dependencies.route_inputs();
+        for (route, inputs) in &previous.routes {
+            if dependencies.is_fresh(route) && !routes.contains_key(route) {
+                routes.insert(route.clone(), inputs.clone());
+            }
+        }
+
+        let global = dependencies.global_inputs();
+        let fingerprints = global
+            .iter()
+            .chain(routes.values().flatten())
+            .map(|path| (path.clone(), modified(path)))
+            .collect();
+
+        BuildCache {
+            fingerprints,
+            global,
+            routes,
+        }
+    }
+
+    /// routes whose inputs haven't changed and whose output still exists in `dir`.
+    /// everything is rebuilt if anything read outside of a route (like the source) changed.
+    pub(crate) fn fresh_routes(&self, dir: &Path) -> HashSet<String> {
+        if self.global.is_empty() || self.global.iter().any(|path| self.changed(path)) {
+            return HashSet::new();
+        }
+
+        self.routes
+            .iter()
+            .filter(|(route, inputs)| {
+                Site::document_path(dir, route).exists()
+                    && !inputs.iter().any(|path| self.changed(path))
+            })
+            .map(|(route, _)| route.clone())
+            .collect()
+    }
+
+    fn changed(&self, path: &Path) -> bool {
+        self.fingerprints.get(path) != Some(&modified(path))
+    }
+
+    fn parse(cache: &str, header: &str) -> Option<Self> {
+        let mut lines = cache.lines();
+        if lines.next()? != header {
+            return None;
+        }
+
+        let mut build = BuildCache::default();
+
+        for line in lines {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                ["input", path, fingerprint] => {
+                    let fingerprint = match *fingerprint {
+                        "-" => None,
+                        f => Some(parse_fingerprint(f)?),
+                    };
+                    build.fingerprints.insert(PathBuf::from(path), fingerprint);
+                }
+                ["global", path] => build.global.push(PathBuf::from(path)),
+                ["route", route] => {
+                    build.routes.insert(String::from(*route), Vec::new());
+                }
+                ["route", route, path] => build
+                    .routes
+                    .entry(String::from(*route))
+                    .or_default()
+                    .push(PathBuf::from(path)),
+                _ => return None,
+            }
+        }
+
+        Some(build)
+    }
+
+    fn serialize(&self, header: &str) -> String {
+        let mut lines = vec![String::from(header)];
+
+        let mut fingerprints: Vec<_> = self.fingerprints.iter().collect();
+        fingerprints.sort();
+        for (path, fingerprint) in fingerprints {
+            let fingerprint = match fingerprint {
+                Some((time, len)) => {
+                    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                    format!("{}.{:09}/{}", since.as_secs(), since.subsec_nanos(), len)
+                }
+                None => String::from("-"),
+            };
+            lines.push(format!("input\t{}\t{}", path.display(), fingerprint));
+        }
+
+        for path in &self.global {
+            lines.push(format!("global\t{}", path.display()));
+        }
+
+        let mut routes: Vec<_> = self.routes.iter().collect();
+        routes.sort();
+        for (route, inputs) in routes {
+            // routes without inputs still need a line, to be known as built
+            lines.push(format!("route\t{}", route));
+            for path in inputs {
+                lines.push(format!("route\t{}\t{}", route, path.display()));
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+/// the version, and a hash of the config (after `--env`), which pages can depend on
+fn header(config: &Config) -> String {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", config).hash(&mut hasher);
+    format!("{} {:016x}", BUILD_CACHE_HEADER, hasher.finish())
+}
+
+/// `secs.nanos/len`
+fn parse_fingerprint(s: &str) -> Option<(SystemTime, u64)> {
+    let (time, len) = s.split_once('/')?;
+    let (secs, nanos) = time.split_once('.')?;
+    let since = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+
+    Some((UNIX_EPOCH + since, len.parse().ok()?))
+}
+
+fn modified(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stale_fingerprint() {
+        let dir = std::env::temp_dir().join(format!("astryx-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("input.md");
+
+        std::fs::write(&file, "a").unwrap();
+        let fingerprint = fingerprint(&[file.clone(), dir.join("missing.md")]);
+        assert!(!is_stale(&fingerprint));
+
+        std::fs::write(&file, "changed").unwrap();
+        assert!(is_stale(&fingerprint));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_cache() {
+        let dir = std::env::temp_dir().join(format!("astryx-build-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::write(dir.join("a/index.html"), "").unwrap();
+        let source = dir.join("site.astryx");
+        let post = dir.join("a.md");
+        std::fs::write(&source, "").unwrap();
+        std::fs::write(&post, "a").unwrap();
+
+        let mut dependencies = Dependencies::default();
+        dependencies.track_input(source.clone());
+        dependencies.start_route("/a");
+        dependencies.track_input(post.clone());
+        dependencies.end_route();
+        dependencies.start_route("/b");
+        dependencies.end_route();
+
+        let config = Config::default();
+        let cache = BuildCache::new(&dependencies, &BuildCache::default());
+        cache.save(&dir, &config).unwrap();
+        let loaded = BuildCache::load(&dir, &config);
+        assert_eq!(loaded, cache);
+
+        // a different config might render every page differently
+        let mut production = config.clone();
+        production.url = Some(String::from("https://example.com"));
+        assert_eq!(BuildCache::load(&dir, &production), BuildCache::default());
+
+        // /b was never written
+        assert_eq!(
+            loaded.fresh_routes(&dir),
+            vec![String::from("/a")].into_iter().collect()
+        );
+
+        std::fs::write(&post, "changed").unwrap();
+        assert!(loaded.fresh_routes(&dir).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::Config;
+use error::{
+    display::{json_error, terminal_error},
+    AstryxError,
+};
+use models::State;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// how diagnostics are printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    /// for people, on stderr
+    Text,
+    /// one json object per line on stdout, for tools
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {}, expected text or json", s)),
+        }
+    }
+}
+
+/// parse and interpret a file, carrying on past errors to return all of them. `state` is
+/// left with everything the file bound.
+pub(crate) fn check(
+    file: &str,
+    path: &str,
+    config: &Config,
+    state: Rc<RefCell<State>>,
+) -> Vec<AstryxError> {
+    if let Err(e) = config.bind(&mut state.borrow_mut()) {
+        return vec![e];
+    }
+
+    let (nodes, errors) = parser::run_all(file, path);
+    let mut errors: Vec<AstryxError> = errors.into_iter().map(AstryxError::from).collect();
+
+    // lines which failed to parse are missing, so anything they bound shows up as unknown
+    errors.extend(interpreter::check(&nodes, state));
+    errors
+}
+
+/// print every error, failing with a count of them if there were any
+pub(crate) fn report(errors: &[AstryxError], path: &str, format: Format) -> Result<(), String> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    for error in errors {
+        match format {
+            Format::Text => eprintln!("{}\n", terminal_error(error, path)),
+            Format::Json => println!("{}", json_error(error, path)),
+        }
+    }
+
+    Err(format!("found {}.", summary(errors.len())))
+}
+
+/// print an error which stops a command, failing with it
+pub(crate) fn fail(error: AstryxError, path: &str, format: Format) -> String {
+    // report only succeeds when there's nothing to report
+    report(&[error], path, format).unwrap_err()
+}
+
+/// "1 error", "2 errors"...
+pub(crate) fn summary(count: usize) -> String {
+    match count {
+        1 => String::from("1 error"),
+        n => format!("{} errors", n),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let config = Config::default();
+        let source = "let a = ]\n@route path=\"/\"\n  %p ${titel}\n  %p ok\n  missing()\n";
+        let errors = check(source, "site.astryx", &config, Default::default());
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| match e {
+                    AstryxError::LocatedError(d) => (d.location.line, d.kind.code()),
+                    _ => panic!("unlocated error"),
+                })
+                .collect::<Vec<_>>(),
+            vec![(1, "E0002"), (3, "E0102"), (5, "E0102")]
+        );
+    }
+
+    #[test]
+    fn test_fail() {
+        let error = AstryxError::Generic(String::from("could not open site.astryx"));
+        assert_eq!(fail(error, "site.astryx", Format::Json), "found 1 error.");
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(summary(1), "1 error");
+        assert_eq!(summary(3), "3 errors");
+    }
+}
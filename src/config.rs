@@ -0,0 +1,480 @@
+// project configuration, read from `astryx.toml` in the working directory
+//
+//     entry = "site.astryx"
+//     output = "build"
+//     static = ["public"]
+//     url = "http://localhost:8888"
+//     comments = true
+//     search = "bloom"
+//
+//     [site]
+//     title = "My Blog"
+//
+//     [env.production]
+//     url = "https://example.com"
+//
+// every key is optional. `[site]` is bound to `site` for templates, along with `url`
+// as `site.url`. `comments` writes source comments into pages as `<!-- -->`. `search` writes
+// a search index of every page with `bloom` or `elasticlunr`. `--env <name>` merges
+// `[env.<name>]` over everything else.
+//
+// only the parts of toml a config file needs are supported: tables, strings, numbers,
+// booleans and single line arrays.
+
+use error::{AstryxError, AstryxResult};
+use models::{Node, Object, State};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+pub(crate) const CONFIG_FILE: &str = "astryx.toml";
+
+const KEYS: &[&str] = &[
+    "entry", "output", "static", "url", "comments", "search", "site", "env",
+];
+
+type Table = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    /// the source file rendered when none is given
+    pub entry: String,
+    /// where `build` writes the site
+    pub output: PathBuf,
+    /// directories served, and copied into the output, as they are
+    pub static_dirs: Vec<PathBuf>,
+    /// the base url of the deployed site
+    pub url: Option<String>,
+    /// whether source comments are written into pages
+    comments: bool,
+    /// the search index written alongside the site, if any
+    pub search: Option<search::Backend>,
+    site: Table,
+    /// the file this was read from, if any
+    path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            entry: String::from("site.astryx"),
+            output: PathBuf::from("build"),
+            static_dirs: vec![PathBuf::from("public")],
+            url: None,
+            comments: false,
+            search: None,
+            site: Table::new(),
+            path: None,
+        }
+    }
+}
+
+impl Config {
+    /// the config in the working directory with overrides for `env`, or the defaults
+    /// if there isn't one
+    pub(crate) fn load(env: Option<&str>) -> AstryxResult<Self> {
+        let path = Path::new(CONFIG_FILE);
+
+        if !path.exists() {
+            return match env {
+                Some(env) => Err(AstryxError::Generic(format!(
+                    "--env {} needs an {} with an [env.{}] table",
+                    env, CONFIG_FILE, env
+                ))),
+                None => Ok(Config::default()),
+            };
+        }
+
+        let source = std::fs::read_to_string(path)?;
+        let config = Config::parse(&source, env)
+            .map_err(|e| AstryxError::Generic(format!("{}: {}", CONFIG_FILE, e)))?;
+
+        Ok(Config {
+            path: Some(path.to_path_buf()),
+            ..config
+        })
+    }
+
+    fn parse(source: &str, env: Option<&str>) -> Result<Self, String> {
+        let mut table = parse(source)?;
+
+        if let Some(env) = env {
+            let overrides = match table.get("env").and_then(|envs| get_table(envs, env)) {
+                Some(overrides) => overrides.clone(),
+                None => return Err(format!("no [env.{}] table", env)),
+            };
+            merge(&mut table, overrides);
+        }
+
+        // after merging, so typos in overrides are caught too
+        if let Some(key) = table.keys().find(|key| !KEYS.contains(&key.as_str())) {
+            return Err(format!(
+                "unknown key {}, expected one of: {}",
+                key,
+                KEYS.join(", ")
+            ));
+        }
+
+        let mut config = Config::default();
+
+        if let Some(entry) = table.get("entry") {
+            config.entry = expect_string(entry, "entry")?;
+        }
+        if let Some(output) = table.get("output") {
+            config.output = expect_string(output, "output")?.into();
+        }
+        if let Some(dirs) = table.get("static") {
+            config.static_dirs = match dirs {
+                Value::Array(dirs) => dirs
+                    .iter()
+                    .map(|dir| expect_string(dir, "static").map(PathBuf::from))
+                    .collect::<Result<_, _>>()?,
+                dir => vec![expect_string(dir, "static")?.into()],
+            };
+        }
+        if let Some(url) = table.get("url") {
+            config.url = Some(expect_string(url, "url")?);
+        }
+        if let Some(comments) = table.get("comments") {
+            config.comments = match comments {
+                Value::Boolean(comments) => *comments,
+                _ => return Err(String::from("comments should be true or false")),
+            };
+        }
+        if let Some(backend) = table.get("search") {
+            config.search = Some(expect_string(backend, "search")?.parse()?);
+        }
+        match table.remove("site") {
+            Some(Value::Table(site)) => config.site = site,
+            Some(_) => return Err(String::from("site should be a table")),
+            None => (),
+        }
+
+        Ok(config)
+    }
+
+    /// make the config available to a source file interpreted with `state`
+    pub(crate) fn bind(&self, state: &mut State) -> AstryxResult<()> {
+        if let Some(path) = &self.path {
+            state.track_input(path);
+        }
+        if self.comments {
+            state.emit_comments();
+        }
+
+        let mut site: HashMap<String, Node<Object>> = self
+            .site
+            .iter()
+            .map(|(key, value)| (key.clone(), Node::new(value.to_object())))
+            .collect();
+
+        if let Some(url) = &self.url {
+            site.entry(String::from("url"))
+                .or_insert_with(|| Node::new(Object::String(url.clone())));
+        }
+
+        state.bind("site", Object::Map(site))
+    }
+}
+
+impl Value {
+    fn to_object(&self) -> Object {
+        match self {
+            Value::String(s) => Object::String(s.clone()),
+            Value::Integer(i) => Object::Number(*i as f64),
+            Value::Float(f) => Object::Number(*f),
+            Value::Boolean(b) => Object::Boolean(*b),
+            Value::Array(values) => {
+                Object::Array(values.iter().map(|v| Node::new(v.to_object())).collect())
+            }
+            Value::Table(table) => Object::Map(
+                table
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Node::new(value.to_object())))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn get_table<'a>(value: &'a Value, key: &str) -> Option<&'a Table> {
+    match value {
+        Value::Table(table) => match table.get(key) {
+            Some(Value::Table(table)) => Some(table),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn expect_string(value: &Value, key: &str) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        _ => Err(format!("{} should be a string", key)),
+    }
+}
+
+/// merge `overrides` into `table`, replacing everything but tables, which are merged
+fn merge(table: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(Value::Table(table)), Value::Table(overrides)) => merge(table, overrides),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<Table, String> {
+    let mut root = Table::new();
+    let mut current: Vec<String> = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let (header, rest) = header
+                .split_once(']')
+                .ok_or_else(|| error(String::from("unclosed table header")))?;
+            expect_end(rest).map_err(error)?;
+
+            current = parse_key(header).map_err(error)?;
+            table_at(&mut root, &current).map_err(error)?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error(String::from("expected key = value")))?;
+        let mut key = parse_key(key).map_err(error)?;
+        let (value, rest) = parse_value(value.trim_start()).map_err(error)?;
+        expect_end(rest).map_err(error)?;
+
+        let name = key.pop().unwrap_or_default();
+        let path: Vec<String> = current.iter().cloned().chain(key).collect();
+        let table = table_at(&mut root, &path).map_err(error)?;
+
+        if table.insert(name.clone(), value).is_some() {
+            return Err(error(format!("{} is defined twice", name)));
+        }
+    }
+
+    Ok(root)
+}
+
+/// the table at a dotted `path`, created if it doesn't exist
+fn table_at<'a>(table: &'a mut Table, path: &[String]) -> Result<&'a mut Table, String> {
+    match path.split_first() {
+        None => Ok(table),
+        Some((key, rest)) => {
+            match table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                Value::Table(table) => table_at(table, rest),
+                _ => Err(format!("{} isn't a table", key)),
+            }
+        }
+    }
+}
+
+/// a dotted key, like `env.production`
+fn parse_key(key: &str) -> Result<Vec<String>, String> {
+    key.split('.')
+        .map(|part| {
+            let part = part.trim();
+            let unquoted = part
+                .strip_prefix('"')
+                .and_then(|p| p.strip_suffix('"'))
+                .unwrap_or(part);
+
+            let bare = part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+            match (unquoted != part, bare && !part.is_empty()) {
+                (true, _) | (_, true) => Ok(String::from(unquoted)),
+                _ => Err(format!("invalid key: {}", key.trim())),
+            }
+        })
+        .collect()
+}
+
+/// parse a value from the start of `s`, returning it and the rest of `s`
+fn parse_value(s: &str) -> Result<(Value, &str), String> {
+    match s.chars().next() {
+        Some('"') => parse_string(&s[1..]),
+        Some('\'') => {
+            let end = s[1..]
+                .find('\'')
+                .ok_or_else(|| String::from("unclosed string"))?;
+            Ok((Value::String(s[1..end + 1].to_string()), &s[end + 2..]))
+        }
+        Some('[') => parse_array(&s[1..]),
+        Some(_) => {
+            let end = s
+                .find(|c: char| c == ',' || c == ']' || c == '#' || c.is_whitespace())
+                .unwrap_or(s.len());
+            let (token, rest) = s.split_at(end);
+
+            let value = match token {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                token => {
+                    let number = token.replace('_', "");
+                    number
+                        .parse()
+                        .map(Value::Integer)
+                        .or_else(|_| number.parse().map(Value::Float))
+                        .map_err(|_| format!("unsupported value: {}", token))?
+                }
+            };
+
+            Ok((value, rest))
+        }
+        None => Err(String::from("missing value")),
+    }
+}
+
+/// a basic string, after its opening quote
+fn parse_string(s: &str) -> Result<(Value, &str), String> {
+    let mut string = String::new();
+    let mut chars = s.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Value::String(string), &s[index + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some('r') => string.push('\r'),
+                Some('"') => string.push('"'),
+                Some('\\') => string.push('\\'),
+                Some(c) => return Err(format!("unsupported escape: \\{}", c)),
+                None => break,
+            },
+            c => string.push(c),
+        }
+    }
+
+    Err(String::from("unclosed string"))
+}
+
+/// an array on a single line, after its opening bracket
+fn parse_array(mut s: &str) -> Result<(Value, &str), String> {
+    let mut values = Vec::new();
+
+    loop {
+        s = s.trim_start();
+        if let Some(rest) = s.strip_prefix(']') {
+            return Ok((Value::Array(values), rest));
+        }
+
+        let (value, rest) = parse_value(s)?;
+        values.push(value);
+        s = rest.trim_start();
+
+        match s.chars().next() {
+            Some(',') => s = &s[1..],
+            Some(']') => (),
+            _ => return Err(String::from("unclosed array (arrays must be on one line)")),
+        }
+    }
+}
+
+/// only whitespace or a comment may follow a value or header
+fn expect_end(rest: &str) -> Result<(), String> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("unexpected {}", rest))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        # a comment
+        entry = "index.astryx"
+        static = ["public", 'assets'] # trailing comment
+        url = "http://localhost:8888"
+        comments = true
+        search = "bloom"
+
+        [site]
+        title = "My \"Blog\""
+        posts = 1_000
+        draft = false
+
+        [site.author]
+        name = "someone"
+
+        [env.production]
+        url = "https://example.com"
+        comments = false
+        site.draft = true
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(CONFIG, None).unwrap();
+        assert_eq!(config.entry, "index.astryx");
+        assert_eq!(config.output, PathBuf::from("build"));
+        assert_eq!(
+            config.static_dirs,
+            vec![PathBuf::from("public"), PathBuf::from("assets")]
+        );
+        assert_eq!(config.url.as_deref(), Some("http://localhost:8888"));
+        assert!(config.comments);
+        assert_eq!(config.search, Some(search::Backend::Bloom));
+        assert_eq!(
+            config.site["title"],
+            Value::String(String::from("My \"Blog\""))
+        );
+        assert_eq!(config.site["posts"], Value::Integer(1000));
+        assert!(matches!(config.site["author"], Value::Table(_)));
+    }
+
+    #[test]
+    fn test_env() {
+        let config = Config::parse(CONFIG, Some("production")).unwrap();
+        assert_eq!(config.url.as_deref(), Some("https://example.com"));
+        assert!(!config.comments);
+        assert_eq!(config.site["draft"], Value::Boolean(true));
+        assert_eq!(config.site["posts"], Value::Integer(1000));
+
+        assert!(Config::parse(CONFIG, Some("staging")).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("a = ").is_err());
+        assert!(parse("a = \"unclosed").is_err());
+        assert!(parse("a = [1, 2").is_err());
+        assert!(parse("a = 1\na = 2").is_err());
+        assert!(parse("a = 1 b").is_err());
+        assert!(parse("[a").is_err());
+        assert!(Config::parse("otput = \"build\"", None).is_err());
+        assert!(Config::parse("entry = 1", None).is_err());
+        assert!(Config::parse("comments = \"yes\"", None).is_err());
+        assert!(Config::parse("search = \"lunr\"", None).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+// `astryx fmt`, which rewrites source files in the style `parser::format` writes: two spaces
+// of indentation, normalised attribute spacing and braces, and single blank lines.
+
+use crate::check::{self, Format};
+use error::AstryxError;
+
+/// format each file in place, or with `check` only report the ones that aren't formatted.
+/// files with syntax errors are left alone.
+pub(crate) fn fmt(paths: &[String], check: bool) -> Result<(), String> {
+    let mut failed = 0;
+    let mut unformatted = 0;
+
+    for path in paths {
+        let source =
+            std::fs::read_to_string(path).map_err(|e| format!("could not open {}: {}", path, e))?;
+
+        let formatted = match parser::format::format(&source, path) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                let errors: Vec<AstryxError> = errors.into_iter().map(AstryxError::from).collect();
+                if let Err(e) = check::report(&errors, path, Format::Text) {
+                    eprintln!("not formatting {}, {}\n", path, e);
+                }
+                failed += 1;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            // stdout is the list of files, for scripts
+            println!("{}", path);
+            eprintln!(
+                "{}:{} is not formatted",
+                path,
+                first_difference(&source, &formatted)
+            );
+            unformatted += 1;
+        } else {
+            std::fs::write(path, formatted)
+                .map_err(|e| format!("could not write {}: {}", path, e))?;
+            eprintln!("formatted: {}", path);
+        }
+    }
+
+    match (failed, unformatted) {
+        (0, 0) => Ok(()),
+        (0, n) => Err(format!("{} not formatted.", files(n))),
+        (n, _) => Err(format!("{} could not be parsed.", files(n))),
+    }
+}
+
+/// the first line (from 1) where two documents differ
+fn first_difference(a: &str, b: &str) -> usize {
+    a.lines().zip(b.lines()).take_while(|(a, b)| a == b).count() + 1
+}
+
+/// "1 file", "2 files"...
+fn files(count: usize) -> String {
+    match count {
+        1 => String::from("1 file"),
+        n => format!("{} files", n),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_templates_are_formatted() {
+        for source in &[
+            include_str!("../templates/blog/site.astryx"),
+            include_str!("../templates/docs/site.astryx"),
+            include_str!("../templates/portfolio/site.astryx"),
+        ] {
+            assert_eq!(&parser::format::format(source, "").unwrap(), source);
+        }
+    }
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!(first_difference("a\nb\nc", "a\nb\nd"), 3);
+        assert_eq!(first_difference("a", "a\nb"), 2);
+    }
+}
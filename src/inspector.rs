@@ -0,0 +1,305 @@
+// debug pages for `astryx serve`, under /__inspect
+//
+// each request runs the whole pipeline again, without the render cache, so the pages
+// always show the source and config as they are on disk along with how long each stage took. stages
+// after a failure are skipped, and the pages for earlier stages still work.
+
+use crate::config::Config;
+use error::{display::display_error, AstryxError, AstryxResult};
+use models::{Node, Object, Site, State};
+use parser::Statement;
+use std::cell::RefCell;
+use std::fs::read_to_string;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub(crate) const PREFIX: &str = "/__inspect";
+
+/// longest value shown before it's cut short
+const MAX_VALUE_LENGTH: usize = 300;
+
+const PAGES: &[(&str, &str)] = &[
+    ("", "overview"),
+    ("/ast", "ast"),
+    ("/nodes", "nodes"),
+    ("/state", "state"),
+    ("/routes", "routes"),
+];
+
+const STYLE: &str = "body{font-family:monospace;margin:2em;background:#111;color:#ddd}\
+    a{color:#8cf}nav a{margin-right:1.5em}td,th{padding:2px 1em 2px 0;text-align:left;vertical-align:top}\
+    ul{list-style:none;padding-left:1.5em;margin:0}summary{cursor:pointer}\
+    pre{white-space:pre-wrap;margin:0 0 0 1.5em;color:#999}\
+    .error{color:#f88;border:1px solid #f88;padding:1em;white-space:pre}";
+
+/// the results of each stage of the pipeline, as far as it got
+#[derive(Default)]
+struct Pipeline<'a> {
+    statements: Option<Vec<Node<Statement<'a>>>>,
+    state: Rc<RefCell<State>>,
+    nodes: Option<Vec<Node<Object>>>,
+    site: Option<Site>,
+    timings: Vec<(&'static str, Duration)>,
+    error: Option<AstryxError>,
+}
+
+impl<'a> Pipeline<'a> {
+    fn run(file: &'a str, path: &'a str, config: &Config) -> Self {
+        let mut pipeline = Pipeline::default();
+        if let Err(e) = pipeline.stages(file, path, config) {
+            pipeline.error = Some(e);
+        }
+        pipeline
+    }
+
+    fn stages(&mut self, file: &'a str, path: &'a str, config: &Config) -> AstryxResult<()> {
+        config.bind(&mut self.state.borrow_mut())?;
+
+        let statements = time(&mut self.timings, "parse", || {
+            parser::run(file, path).map_err(AstryxError::from)
+        })?;
+        let statements = self.statements.insert(statements);
+
+        let state = Rc::clone(&self.state);
+        let nodes = time(&mut self.timings, "interpret", || {
+            interpreter::run(statements, state)
+        })?;
+        let nodes = self.nodes.insert(nodes);
+
+        let site = time(&mut self.timings, "render", || Site::render(nodes.clone()));
+        self.site = Some(site);
+
+        Ok(())
+    }
+}
+
+fn time<T>(
+    timings: &mut Vec<(&'static str, Duration)>,
+    stage: &'static str,
+    f: impl FnOnce() -> T,
+) -> T {
+    let start = Instant::now();
+    let result = f();
+    timings.push((stage, start.elapsed()));
+    result
+}
+
+/// the inspector page at `request_path`, or `None` if there isn't one
+pub(crate) fn page(path: &str, request_path: &str, env: Option<&str>) -> Option<String> {
+    let page = request_path.strip_prefix(PREFIX)?.trim_end_matches('/');
+    let (_, title) = PAGES.iter().find(|(p, _)| *p == page)?;
+
+    let start = Instant::now();
+    let source = Config::load(env).and_then(|config| Ok((config, read_to_string(path)?)));
+
+    let body = match source {
+        Ok((config, file)) => {
+            let pipeline = Pipeline::run(&file, path, &config);
+            let content = match page {
+                "/ast" => pipeline.statements.as_deref().map(statement_tree),
+                "/nodes" => pipeline.nodes.as_deref().map(node_tree),
+                "/state" => pipeline
+                    .nodes
+                    .as_ref()
+                    .map(|_| state_table(&pipeline.state.borrow())),
+                "/routes" => pipeline.site.as_ref().map(route_list),
+                _ => Some(timing_table(&pipeline.timings, start.elapsed())),
+            };
+
+            let error = pipeline
+                .error
+                .map(|e| error_box(&display_error(&e, path)))
+                .unwrap_or_default();
+
+            error + &content.unwrap_or_else(|| String::from("<p>not reached.</p>"))
+        }
+        Err(e) => error_box(&display_error(&e, path)),
+    };
+
+    Some(layout(title, path, &body))
+}
+
+fn layout(title: &str, path: &str, body: &str) -> String {
+    let nav: String = PAGES
+        .iter()
+        .map(|(page, title)| format!("<a href=\"{}{}\">{}</a>", PREFIX, page, title))
+        .collect();
+
+    format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>{title} - {path}</title>\
+         <style>{style}</style></head><body><nav>{nav}</nav><h1>{title}</h1><p>{path}</p>{body}</body></html>",
+        title = title,
+        path = escape(path),
+        style = STYLE,
+        nav = nav,
+        body = body
+    )
+}
+
+fn error_box(error: &str) -> String {
+    format!("<div class=\"error\">{}</div>", escape(error))
+}
+
+fn timing_table(timings: &[(&str, Duration)], total: Duration) -> String {
+    let rows: String = timings
+        .iter()
+        .chain(std::iter::once(&("total", total)))
+        .map(|(stage, duration)| format!("<tr><td>{}</td><td>{:.2?}</td></tr>", stage, duration))
+        .collect();
+
+    format!(
+        "<table><tr><th>stage</th><th>time</th></tr>{}</table>",
+        rows
+    )
+}
+
+fn statement_tree(statements: &[Node<Statement>]) -> String {
+    tree(statements, &|statement: &Statement| {
+        // the variant name, with everything else shown when expanded
+        let debug = format!("{:#?}", statement);
+        let name = debug
+            .split(['(', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        (name, Some(debug))
+    })
+}
+
+fn node_tree(nodes: &[Node<Object>]) -> String {
+    tree(nodes, &|object: &Object| (summary(object), None))
+}
+
+/// nested lists of nodes, labelled by `label` with optional details
+fn tree<T>(nodes: &[Node<T>], label: &dyn Fn(&T) -> (String, Option<String>)) -> String {
+    let items: String = nodes
+        .iter()
+        .map(|node| {
+            let (summary, details) = label(&node.borrow());
+            let children: Vec<Node<T>> = node.children().collect();
+            let details = details
+                .map(|d| format!("<pre>{}</pre>", escape(&d)))
+                .unwrap_or_default();
+
+            if details.is_empty() && children.is_empty() {
+                format!("<li>{}</li>", escape(&summary))
+            } else {
+                format!(
+                    "<li><details open><summary>{}</summary>{}{}</details></li>",
+                    escape(&summary),
+                    details,
+                    tree(&children, label)
+                )
+            }
+        })
+        .collect();
+
+    format!("<ul>{}</ul>", items)
+}
+
+/// a one line description of an evaluated object
+fn summary(object: &Object) -> String {
+    match object {
+        Object::HTMLElement(e) => e.open_tag(),
+        Object::HTMLPage(route) => format!("@route path={}", route),
+        Object::BuiltinFunction(_) => String::from("builtin function"),
+        Object::String(s) => truncate(&format!("{:?}", s)),
+        _ => truncate(&format!("{:?}", object)),
+    }
+}
+
+fn state_table(state: &State) -> String {
+    let mut variables: Vec<(&String, &Object)> = state.local.iter().collect();
+    variables.sort_by_key(|(name, _)| name.as_str());
+
+    // builtins are listed last, by name only
+    let (builtins, variables): (Vec<_>, Vec<_>) = variables
+        .into_iter()
+        .partition(|(_, value)| matches!(value, Object::BuiltinFunction(_)));
+
+    let rows: String = variables
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(name),
+                escape(&summary(value))
+            )
+        })
+        .collect();
+
+    let builtins = builtins
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+
+    format!(
+        "<table><tr><th>name</th><th>value</th></tr>{}</table><h2>builtins</h2><p>{}</p>",
+        rows,
+        escape(&builtins)
+    )
+}
+
+fn route_list(site: &Site) -> String {
+    let mut routes: Vec<(&String, &String)> = site.documents.iter().collect();
+    routes.sort();
+
+    let rows: String = routes
+        .iter()
+        .map(|(route, document)| {
+            let url = format!(
+                "/{}",
+                route.trim_start_matches("./").trim_start_matches('/')
+            );
+            format!(
+                "<tr><td><a href=\"{}\">{}</a></td><td>{} bytes</td></tr>",
+                escape(&url),
+                escape(route),
+                document.len()
+            )
+        })
+        .collect();
+
+    format!(
+        "<table><tr><th>route</th><th>size</th></tr>{}</table>",
+        rows
+    )
+}
+
+fn truncate(s: &str) -> String {
+    match s.char_indices().nth(MAX_VALUE_LENGTH) {
+        Some((index, _)) => format!("{}…", &s[..index]),
+        None => s.to_string(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_statement_tree() {
+        let statements = parser::run("%div\n  %p hi\n", "test.astryx").unwrap();
+        let html = statement_tree(&statements);
+
+        assert!(html.starts_with("<ul><li><details open><summary>Element</summary>"));
+        assert_eq!(html.matches("<summary>Element</summary>").count(), 2);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short"), "short");
+        assert_eq!(
+            truncate(&"é".repeat(400)).chars().count(),
+            MAX_VALUE_LENGTH + 1
+        );
+    }
+}
@@ -0,0 +1,53 @@
+// astryx live reload client, injected into pages by `astryx serve`
+(function () {
+  var OVERLAY_ID = "astryx-error-overlay";
+  var source = new EventSource(
+    location.protocol + "//" + location.hostname + ":LIVERELOAD_PORT/"
+  );
+
+  function hideOverlay() {
+    var overlay = document.getElementById(OVERLAY_ID);
+    if (overlay) overlay.parentNode.removeChild(overlay);
+  }
+
+  function showOverlay(message) {
+    hideOverlay();
+
+    var overlay = document.createElement("div");
+    overlay.id = OVERLAY_ID;
+    overlay.setAttribute(
+      "style",
+      "position:fixed;top:0;left:0;right:0;bottom:0;z-index:2147483647;overflow:auto;" +
+        "padding:2em;background:rgba(0,0,0,0.9);color:#fff;font-family:monospace;"
+    );
+
+    var title = document.createElement("h1");
+    title.textContent = "Error :(";
+
+    var pre = document.createElement("pre");
+    pre.textContent = message;
+
+    overlay.appendChild(title);
+    overlay.appendChild(pre);
+    document.body.appendChild(overlay);
+  }
+
+  source.addEventListener("reload", function () {
+    location.reload();
+  });
+
+  // bust the cache on every stylesheet instead of reloading the page
+  source.addEventListener("css", function () {
+    hideOverlay();
+    var links = document.querySelectorAll('link[rel="stylesheet"]');
+    for (var i = 0; i < links.length; i++) {
+      var url = new URL(links[i].href);
+      url.searchParams.set("livereload", Date.now());
+      links[i].href = url.toString();
+    }
+  });
+
+  source.addEventListener("builderror", function (event) {
+    showOverlay(event.data);
+  });
+})();
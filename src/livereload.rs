@@ -0,0 +1,305 @@
+// live reload for `astryx serve`
+//
+// a background thread polls the project for changes and rebuilds the site. every page
+// served in development gets a small script which listens to a server-sent events
+// endpoint on the next port up, and reloads the page, swaps stylesheets or shows an
+// error overlay depending on how the rebuild went.
+//
+// the events need their own listener because simple_server handles one connection at
+// a time and can't stream a response.
+
+use crate::cache::RenderCache;
+use error::{display::display_error, AstryxResult};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// directories under the project root which never trigger a rebuild, along with the
+/// paths `start` is given
+const IGNORED_DIRS: &[&str] = &["target", "node_modules"];
+
+type Clients = Arc<Mutex<Vec<TcpStream>>>;
+
+enum Event {
+    Reload,
+    /// only stylesheets changed, so pages can swap them without reloading
+    Css,
+    Error(String),
+}
+
+impl Event {
+    fn to_message(&self) -> String {
+        match self {
+            Event::Reload => String::from("event: reload\ndata:\n\n"),
+            Event::Css => String::from("event: css\ndata:\n\n"),
+            // each line of the payload needs its own data field
+            Event::Error(e) => format!(
+                "event: builderror\n{}\n",
+                e.lines()
+                    .map(|line| format!("data: {}\n", line))
+                    .collect::<String>()
+            ),
+        }
+    }
+}
+
+/// watch the project containing `path` (apart from the `ignored` files and directories),
+/// rebuild it into `cache` on changes and notify pages listening on `host:port`
+pub(crate) fn start(
+    path: String,
+    host: &str,
+    port: u32,
+    ignored: Vec<PathBuf>,
+    cache: Arc<Mutex<RenderCache>>,
+) -> AstryxResult<()> {
+    let listener = TcpListener::bind(format!("{}:{}", host, port))?;
+    let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+
+    let accepting = Arc::clone(&clients);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Ok(stream) = subscribe(stream) {
+                accepting.lock().unwrap().push(stream);
+            }
+        }
+    });
+
+    let ignored: Vec<PathBuf> = ignored.iter().map(|path| relative(path)).collect();
+    thread::spawn(move || watch(&path, &ignored, clients, cache));
+
+    Ok(())
+}
+
+/// answer an event stream request and keep the connection for broadcasts
+fn subscribe(mut stream: TcpStream) -> std::io::Result<TcpStream> {
+    // the request itself doesn't matter, but it has to be read before responding
+    stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+    let _ = stream.read(&mut [0; 4096]);
+
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\n\
+          content-type: text/event-stream\r\n\
+          cache-control: no-cache\r\n\
+          access-control-allow-origin: *\r\n\r\n\
+          : connected\n\n",
+    )?;
+
+    Ok(stream)
+}
+
+fn broadcast(clients: &Clients, event: &Event) {
+    let message = event.to_message();
+
+    // disconnected pages fail to write and are dropped
+    clients
+        .lock()
+        .unwrap()
+        .retain(|mut client| client.write_all(message.as_bytes()).is_ok());
+}
+
+fn watch(path: &str, ignored: &[PathBuf], clients: Clients, cache: Arc<Mutex<RenderCache>>) {
+    let mut files = snapshot(path, ignored);
+    let mut failed = false;
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let current = snapshot(path, ignored);
+        let changed = changed_files(&files, &current);
+        files = current;
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        // rendering here also warms the cache for the reloaded page
+        let event = match cache.lock().unwrap().render(path) {
+            Err(e) => Event::Error(display_error(&e, path)),
+            // a page showing an error needs a full reload to recover
+            Ok(_) if !failed && changed.iter().all(|f| has_extension(f, "css")) => Event::Css,
+            Ok(_) => Event::Reload,
+        };
+
+        failed = matches!(event, Event::Error(_));
+        println!("{} changed, reloading", display_paths(&changed));
+        broadcast(&clients, &event);
+    }
+}
+
+/// modification times of the source file and everything in the working directory,
+/// which covers anything it reads as well as `public/`
+fn snapshot(path: &str, ignored: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    insert_modified(&mut files, Path::new(path));
+    walk(Path::new("."), ignored, &mut files);
+    files
+}
+
+fn walk(dir: &Path, ignored: &[PathBuf], files: &mut HashMap<PathBuf, SystemTime>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if name.starts_with('.') || ignored.contains(&relative(&path)) {
+            continue;
+        }
+
+        if path.is_dir() {
+            if !IGNORED_DIRS.contains(&name.as_str()) {
+                walk(&path, ignored, files);
+            }
+        } else {
+            insert_modified(files, &path);
+        }
+    }
+}
+
+fn insert_modified(files: &mut HashMap<PathBuf, SystemTime>, path: &Path) {
+    // so the source file and its walked entry are the same key
+    let path = path.strip_prefix(".").unwrap_or(path);
+
+    if let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) {
+        files.insert(path.to_path_buf(), modified);
+    }
+}
+
+/// `path` relative to the working directory without any `.`, so `./build` and `build`
+/// are the same
+fn relative(path: &Path) -> PathBuf {
+    let path = std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path);
+
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// files added, removed or modified between two snapshots
+fn changed_files(
+    before: &HashMap<PathBuf, SystemTime>,
+    after: &HashMap<PathBuf, SystemTime>,
+) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = after
+        .iter()
+        .filter(|(path, modified)| before.get(*path) != Some(modified))
+        .map(|(path, _)| path.clone())
+        .chain(
+            before
+                .keys()
+                .filter(|path| !after.contains_key(*path))
+                .cloned(),
+        )
+        .collect();
+
+    changed.sort();
+    changed
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|e| e == extension)
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// add the live reload client to a served html page
+pub(crate) fn inject(html: &str, port: u32) -> String {
+    let script = format!(
+        "<script>{}</script>",
+        include_str!("livereload.js").replace("LIVERELOAD_PORT", &port.to_string())
+    );
+
+    match html.rfind("</body>") {
+        Some(index) => [&html[..index], &script, &html[index..]].concat(),
+        None => [html, &script].concat(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_changed_files() {
+        let now = SystemTime::now();
+        let later = now + Duration::from_secs(1);
+
+        let before: HashMap<PathBuf, SystemTime> = vec![
+            (PathBuf::from("a.astryx"), now),
+            (PathBuf::from("b.md"), now),
+            (PathBuf::from("c.css"), now),
+        ]
+        .into_iter()
+        .collect();
+        let after: HashMap<PathBuf, SystemTime> = vec![
+            (PathBuf::from("a.astryx"), now),
+            (PathBuf::from("c.css"), later),
+            (PathBuf::from("d.md"), now),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            changed_files(&before, &after),
+            vec![
+                PathBuf::from("b.md"),
+                PathBuf::from("c.css"),
+                PathBuf::from("d.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_ignores_output() {
+        let dir = PathBuf::from(format!("target/astryx-watch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("site")).unwrap();
+        std::fs::write(dir.join("page.md"), "").unwrap();
+        std::fs::write(dir.join("site/index.html"), "").unwrap();
+
+        let ignored = [relative(&dir.join("site"))];
+        let mut files = HashMap::new();
+        walk(&Path::new(".").join(&dir), &ignored, &mut files);
+
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec![&dir.join("page.md")]);
+        assert_eq!(relative(Path::new("./build/.")), PathBuf::from("build"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_error_event() {
+        assert_eq!(
+            Event::Error(String::from("line one\nline two")).to_message(),
+            "event: builderror\ndata: line one\ndata: line two\n\n"
+        );
+    }
+
+    #[test]
+    fn test_inject() {
+        assert_eq!(
+            inject("<html><body>hi</body></html>", 1)
+                .matches("<script>")
+                .count(),
+            1
+        );
+        assert!(inject("<p>hi</p>", 1).starts_with("<p>hi</p><script>"));
+    }
+}
@@ -0,0 +1,496 @@
+// what the language server knows about an open document. each version of the text is
+// checked like `astryx check` does, and the state the interpreter ends with answers
+// completion, hover and definition requests.
+
+use super::protocol::{line_and_column, path_to_uri, range};
+use crate::config::Config;
+use error::{AstryxError, Location};
+use models::{Node, Object, State};
+use parser::{Expression, Literal, Statement};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+// lsp enums
+const SEVERITY_ERROR: u8 = 1;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_FIELD: u8 = 5;
+const COMPLETION_VARIABLE: u8 = 6;
+const SYMBOL_FILE: u8 = 1;
+
+/// longest value shown in a hover before it's cut short
+const MAX_PREVIEW_LENGTH: usize = 80;
+
+pub(crate) struct Document {
+    uri: String,
+    /// the path given to the parser, so errors in this document can be told apart
+    path: String,
+    text: String,
+    state: Rc<RefCell<State>>,
+    errors: Vec<AstryxError>,
+}
+
+impl Document {
+    pub(crate) fn new(uri: String, path: String, text: String, env: Option<&str>) -> Self {
+        let state = Rc::new(RefCell::new(State::new()));
+        let (config, mut errors) = match Config::load(env) {
+            Ok(config) => (config, Vec::new()),
+            Err(e) => (Config::default(), vec![e]),
+        };
+
+        errors.extend(crate::check::check(
+            &text,
+            &path,
+            &config,
+            Rc::clone(&state),
+        ));
+
+        Document {
+            uri,
+            path,
+            text,
+            state,
+            errors,
+        }
+    }
+
+    /// a `textDocument/publishDiagnostics` notification for every error
+    pub(crate) fn diagnostics(&self) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {
+                "uri": self.uri,
+                "diagnostics": self.errors.iter().map(|e| self.diagnostic(e)).collect::<Vec<Value>>(),
+            },
+        })
+    }
+
+    fn diagnostic(&self, error: &AstryxError) -> Value {
+        let start = range(&self.text, 1, 1, 0);
+
+        let d = match error {
+            AstryxError::LocatedError(d) => d,
+            AstryxError::Generic(message) => return plain_diagnostic(start, message),
+            AstryxError::IO(e) => return plain_diagnostic(start, &e.to_string()),
+        };
+
+        let mut message = d.kind.message();
+        if let Some(help) = &d.help {
+            message = format!("{}\nhelp: {}", message, help);
+        }
+
+        // errors in other files, like astryx.toml, are shown at the top of this one
+        let range = match self.is_here(&d.location) {
+            true => self.range(&d.location),
+            false => {
+                message = format!(
+                    "{}:{}:{}: {}",
+                    d.location.filename(),
+                    d.location.line,
+                    d.location.column,
+                    message
+                );
+                start
+            }
+        };
+
+        let related: Vec<Value> = d
+            .labels
+            .iter()
+            .filter(|(location, _)| self.is_here(location))
+            .map(|(location, label)| {
+                json!({
+                    "location": { "uri": self.uri, "range": self.range(location) },
+                    "message": label,
+                })
+            })
+            .collect();
+
+        json!({
+            "range": range,
+            "severity": SEVERITY_ERROR,
+            "code": d.kind.code(),
+            "source": "astryx",
+            "message": message,
+            "relatedInformation": related,
+        })
+    }
+
+    /// builtins and variables, or the keys of a map after a `.`
+    pub(crate) fn completions(&self, position: &Value) -> Value {
+        let (line, column) = match line_and_column(&self.text, position) {
+            Some(found) => found,
+            None => return json!([]),
+        };
+
+        let before: String = line.chars().take(column).collect();
+        let chain = &before[before.len() - trailing_chain(&before)..];
+
+        let (candidates, partial): (Vec<(String, Object)>, &str) = match chain.rfind('.') {
+            Some(dot) => match self.evaluate(&chain[..dot]) {
+                Some(Object::Map(map)) => (
+                    map.iter()
+                        .map(|(k, v)| (k.clone(), v.borrow().clone()))
+                        .collect(),
+                    &chain[dot + 1..],
+                ),
+                _ => return json!([]),
+            },
+            None => {
+                let state = self.state.borrow();
+                let mut names = state.names();
+                names.sort();
+                names.dedup();
+                let candidates = names
+                    .into_iter()
+                    .filter_map(|name| state.get(&name).map(|value| (name, value)))
+                    .collect();
+                (candidates, chain)
+            }
+        };
+
+        let in_map = chain.contains('.');
+        let docs = self.docs();
+        let items: Vec<Value> = candidates
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(partial))
+            .map(|(name, value)| {
+                let kind = match (&value, in_map) {
+                    (Object::BuiltinFunction(_), _) => COMPLETION_FUNCTION,
+                    (_, true) => COMPLETION_FIELD,
+                    (_, false) => COMPLETION_VARIABLE,
+                };
+                let mut item = json!({ "label": name, "kind": kind, "detail": value.type_name() });
+                if let Some(doc) = self.documentation(&docs, &name).filter(|_| !in_map) {
+                    item["documentation"] = json!(doc);
+                }
+                item
+            })
+            .collect();
+
+        json!(items)
+    }
+
+    /// the type of the variable or key under the cursor, with its doc comments and a preview of
+    /// its value
+    pub(crate) fn hover(&self, position: &Value) -> Value {
+        let (line, column) = match line_and_column(&self.text, position) {
+            Some(found) => found,
+            None => return Value::Null,
+        };
+
+        let (start, chain) = chain_at(line, column);
+        let value = match self.evaluate(&chain) {
+            Some(value) => value,
+            None => return Value::Null,
+        };
+
+        let mut contents = format!("`{}`: {}", chain, value.type_name());
+        if let Some(doc) = self.documentation(&self.docs(), &chain) {
+            contents.push_str(&format!("\n\n{}", doc));
+        }
+        match &value {
+            Object::Map(map) => {
+                let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+                keys.sort();
+                contents.push_str(&format!("\n\nkeys: {}", keys.join(", ")));
+            }
+            Object::BuiltinFunction(_) | Object::None => {}
+            value => contents.push_str(&format!("\n\n```\n{}\n```", preview(&value.inspect()))),
+        }
+
+        let line_number = position["line"].as_u64().unwrap_or(0) as u32 + 1;
+        json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": range(&self.text, line_number, start + 1, chain.chars().count()),
+        })
+    }
+
+    /// where the variable under the cursor was bound, or the file a relative path points to
+    pub(crate) fn definition(&self, position: &Value) -> Value {
+        let (line, column) = match line_and_column(&self.text, position) {
+            Some(found) => found,
+            None => return Value::Null,
+        };
+
+        if let Some(path) = path_at(line, column).filter(|path| Path::new(path).is_file()) {
+            return json!({
+                "uri": path_to_uri(Path::new(&path)),
+                "range": range("", 1, 1, 0),
+            });
+        }
+
+        let (_, chain) = chain_at(line, column);
+        let variable = chain.split('.').next().unwrap_or_default();
+
+        match self.state.borrow().location(variable) {
+            Some(location) if self.is_here(&location) => json!({
+                "uri": self.uri,
+                "range": self.range(&location),
+            }),
+            Some(location) => {
+                let text = std::fs::read_to_string(location.filename()).unwrap_or_default();
+                json!({
+                    "uri": path_to_uri(Path::new(location.filename())),
+                    "range": range(&text, location.line, location.column, location.length),
+                })
+            }
+            None => Value::Null,
+        }
+    }
+
+    /// every route, named by its path
+    pub(crate) fn symbols(&self) -> Value {
+        let (nodes, _) = parser::run_all(&self.text, &self.path);
+        let mut symbols = Vec::new();
+
+        for node in nodes {
+            self.route_symbols(&node, &mut symbols);
+        }
+
+        json!(symbols)
+    }
+
+    fn route_symbols(&self, node: &Node<Statement>, symbols: &mut Vec<Value>) {
+        if let Statement::Route(route) = &*node.borrow() {
+            if *route.ident.fragment() == "route" {
+                let name = route
+                    .attributes
+                    .iter()
+                    .find(|(k, _)| *k.fragment() == "path")
+                    .map(|(_, path)| match path {
+                        Expression::Literal(Literal::String(s)) => s.fragment().to_string(),
+                        path => path.inspect(),
+                    })
+                    .unwrap_or_else(|| String::from("(no path)"));
+
+                let ident = Location::from(route.ident);
+                let line = self.text.lines().nth(ident.line as usize - 1).unwrap_or("");
+
+                // from the `@` to the end of the line
+                symbols.push(json!({
+                    "name": name,
+                    "kind": SYMBOL_FILE,
+                    "range": range(
+                        &self.text,
+                        ident.line,
+                        ident.column - 1,
+                        line.chars().count() + 2 - ident.column,
+                    ),
+                    "selectionRange": self.range(&ident),
+                }));
+            }
+        }
+
+        for child in node.children() {
+            self.route_symbols(&child, symbols);
+        }
+    }
+
+    /// where each documented `let` in this document is, and its doc comments
+    fn docs(&self) -> Vec<(Location, String)> {
+        let (nodes, _) = parser::run_all(&self.text, &self.path);
+        parser::docs::documented(&nodes)
+            .into_iter()
+            .map(|documented| (Location::from(documented.ident), documented.doc))
+            .collect()
+    }
+
+    /// the doc comments above the `let` a variable was bound by, if it has any
+    fn documentation(&self, docs: &[(Location, String)], name: &str) -> Option<String> {
+        let location = self.state.borrow().location(name)?;
+        docs.iter()
+            .find(|(documented, _)| *documented == location)
+            .map(|(_, doc)| doc.clone())
+    }
+
+    /// the value of a variable or a chain of keys like `post.meta.title`
+    fn evaluate(&self, chain: &str) -> Option<Object> {
+        let mut parts = chain.split('.');
+        let mut value = self.state.borrow().get(parts.next()?)?;
+
+        for key in parts {
+            value = match value {
+                Object::Map(map) => map.get(key)?.borrow().clone(),
+                _ => return None,
+            };
+        }
+
+        Some(value)
+    }
+
+    fn is_here(&self, location: &Location) -> bool {
+        location.filename() == self.path
+    }
+
+    fn range(&self, location: &Location) -> Value {
+        range(&self.text, location.line, location.column, location.length)
+    }
+}
+
+fn plain_diagnostic(range: Value, message: &str) -> Value {
+    json!({
+        "range": range,
+        "severity": SEVERITY_ERROR,
+        "source": "astryx",
+        "message": message,
+    })
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// the length in bytes of the variable and keys at the end of `s`, eg. `post.me` in `%p ${post.me`
+fn trailing_chain(s: &str) -> usize {
+    s.chars()
+        .rev()
+        .take_while(|c| is_ident(*c) || *c == '.')
+        .map(char::len_utf8)
+        .sum()
+}
+
+/// the chain of keys up to the end of the word at a character column, and where it starts
+fn chain_at(line: &str, column: usize) -> (usize, String) {
+    let chars: Vec<char> = line.chars().collect();
+
+    let mut start = column.min(chars.len());
+    while start > 0 && (is_ident(chars[start - 1]) || chars[start - 1] == '.') {
+        start -= 1;
+    }
+
+    let mut end = column.min(chars.len());
+    while end < chars.len() && is_ident(chars[end]) {
+        end += 1;
+    }
+
+    let chain: String = chars[start..end].iter().collect();
+    (start, chain.trim_matches('.').to_string())
+}
+
+/// a `./` or `../` path around a character column
+fn path_at(line: &str, column: usize) -> Option<String> {
+    let is_path = |c: &char| !c.is_whitespace() && !"()\"',{}=".contains(*c);
+    let chars: Vec<char> = line.chars().collect();
+
+    let mut start = column.min(chars.len());
+    while start > 0 && is_path(&chars[start - 1]) {
+        start -= 1;
+    }
+
+    let path: String = chars[start..].iter().take_while(|c| is_path(c)).collect();
+    Some(path).filter(|path| path.starts_with("./") || path.starts_with("../"))
+}
+
+fn preview(value: &str) -> String {
+    match value.char_indices().nth(MAX_PREVIEW_LENGTH) {
+        Some((end, _)) => format!("{}...", &value[..end]),
+        None => value.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = "--- shown on every page
+let title = \"hello\"
+let post = frontmatter()
+@route path=\"/\"
+  %p ${titel}
+@route path=title
+";
+
+    fn document() -> Document {
+        Document::new(
+            String::from("file:///site.astryx"),
+            String::from("site.astryx"),
+            SOURCE.into(),
+            None,
+        )
+    }
+
+    fn labels(items: &Value) -> Vec<&str> {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = &document().diagnostics()["params"]["diagnostics"];
+        let titel = diagnostics
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|d| d["code"] == "E0102")
+            .unwrap();
+
+        assert_eq!(
+            titel["range"],
+            json!({"start": {"line": 4, "character": 7}, "end": {"line": 4, "character": 12}})
+        );
+        assert_eq!(
+            titel["relatedInformation"][0]["message"],
+            "`title` defined here"
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        let document = document();
+        let items = document.completions(&json!({"line": 4, "character": 8}));
+        assert_eq!(labels(&items), vec!["title", "toc"]);
+        assert_eq!(items[0]["kind"], COMPLETION_VARIABLE);
+        assert_eq!(items[0]["documentation"], "shown on every page");
+        assert_eq!(items[1]["kind"], COMPLETION_FUNCTION);
+    }
+
+    #[test]
+    fn test_hover_and_definition() {
+        let document = document();
+        let hover = document.hover(&json!({"line": 5, "character": 14}));
+        assert!(hover["contents"]["value"]
+            .as_str()
+            .unwrap()
+            .starts_with("`title`: a string\n\nshown on every page\n\n"));
+
+        assert_eq!(
+            document.definition(&json!({"line": 5, "character": 14})),
+            json!({
+                "uri": "file:///site.astryx",
+                "range": {"start": {"line": 1, "character": 4}, "end": {"line": 1, "character": 9}},
+            })
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        let symbols = document().symbols();
+        assert_eq!(symbols[0]["name"], "/");
+        assert_eq!(symbols[1]["name"], "title");
+        assert_eq!(
+            symbols[0]["range"],
+            json!({"start": {"line": 3, "character": 0}, "end": {"line": 3, "character": 15}})
+        );
+    }
+
+    #[test]
+    fn test_chains() {
+        assert_eq!(trailing_chain("%p ${post.me"), "post.me".len());
+        assert_eq!(
+            chain_at("%p ${post.meta.title}", 11),
+            (5, String::from("post.meta"))
+        );
+        assert_eq!(
+            path_at("load(path: ./posts/a.md)", 14),
+            Some(String::from("./posts/a.md"))
+        );
+        assert_eq!(path_at("%p hi", 3), None);
+    }
+}
@@ -0,0 +1,235 @@
+// `astryx lsp`, a language server for editors, speaking json-rpc over stdin and stdout.
+//
+// documents are synced in full on every change, then checked and their diagnostics
+// published. nothing else may be printed to stdout while this runs.
+
+mod analysis;
+mod protocol;
+
+use analysis::Document;
+use error::{AstryxError, AstryxResult};
+use protocol::{read_message, uri_to_path, write_message};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+// json-rpc error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+struct Server<W: Write> {
+    output: W,
+    env: Option<String>,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+/// serve requests on stdin until the client exits
+pub(crate) fn run(env: Option<String>) -> AstryxResult<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    let mut server = Server {
+        output: stdout.lock(),
+        env,
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+
+    server.serve(&mut stdin.lock())
+}
+
+impl<W: Write> Server<W> {
+    fn serve<R: BufRead>(&mut self, input: &mut R) -> AstryxResult<()> {
+        while let Some(message) = read_message(input)? {
+            let method = message["method"].as_str().unwrap_or_default();
+
+            if method == "exit" {
+                return match self.shutdown {
+                    true => Ok(()),
+                    false => Err(AstryxError::Generic(String::from(
+                        "exit before shutdown request",
+                    ))),
+                };
+            }
+
+            let params = &message["params"];
+            let result = match method {
+                "initialize" => Some(self.initialize(params)),
+                "shutdown" => {
+                    self.shutdown = true;
+                    Some(Ok(Value::Null))
+                }
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+                    self.update(&document["uri"], &document["text"])?;
+                    None
+                }
+                "textDocument/didChange" => {
+                    // full sync, so the last change is the whole document
+                    let text = &params["contentChanges"][0]["text"];
+                    self.update(&params["textDocument"]["uri"], text)?;
+                    None
+                }
+                "textDocument/didClose" => {
+                    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                    self.documents.remove(uri);
+                    None
+                }
+                "textDocument/completion" => {
+                    Some(self.with_document(params, |d, position| d.completions(position)))
+                }
+                "textDocument/hover" => Some(self.with_document(params, Document::hover)),
+                "textDocument/definition" => Some(self.with_document(params, Document::definition)),
+                "textDocument/documentSymbol" => {
+                    Some(self.with_document(params, |d, _| d.symbols()))
+                }
+                _ => Some(Err((
+                    METHOD_NOT_FOUND,
+                    format!("unsupported method {}", method),
+                ))),
+            };
+
+            // notifications (without an id) are never answered
+            if let (Some(id), Some(result)) = (message.get("id"), result) {
+                let response = match result {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+                write_message(&mut self.output, &response)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn initialize(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        // relative paths in documents, and astryx.toml, are found from the workspace root
+        if let Some(root) = params["rootUri"].as_str().and_then(uri_to_path) {
+            if let Err(e) = std::env::set_current_dir(&root) {
+                eprintln!("could not open workspace {}: {}", root.display(), e);
+            }
+        }
+
+        Ok(json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "completionProvider": { "triggerCharacters": [".", "{"] },
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "documentSymbolProvider": true,
+            },
+            "serverInfo": { "name": "astryx", "version": env!("CARGO_PKG_VERSION") },
+        }))
+    }
+
+    /// check a new version of a document and publish its diagnostics
+    fn update(&mut self, uri: &Value, text: &Value) -> AstryxResult<()> {
+        let (uri, text) = match (uri.as_str(), text.as_str()) {
+            (Some(uri), Some(text)) => (uri, text),
+            _ => return Ok(()),
+        };
+
+        let document = Document::new(
+            uri.into(),
+            document_path(uri),
+            text.into(),
+            self.env.as_deref(),
+        );
+        write_message(&mut self.output, &document.diagnostics())?;
+        self.documents.insert(uri.into(), document);
+
+        Ok(())
+    }
+
+    fn with_document<F: Fn(&Document, &Value) -> Value>(
+        &self,
+        params: &Value,
+        f: F,
+    ) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match self.documents.get(uri) {
+            Some(document) => Ok(f(document, &params["position"])),
+            None => Err((INVALID_PARAMS, format!("{} is not open", uri))),
+        }
+    }
+}
+
+/// the path of a document relative to the workspace, as `astryx check` would be given it
+fn document_path(uri: &str) -> String {
+    let path = match uri_to_path(uri) {
+        Some(path) => path,
+        None => return uri.into(),
+    };
+
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf));
+
+    relative.unwrap_or(path).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn message(value: Value) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &value).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_session() {
+        let input: Vec<u8> = [
+            message(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})),
+            message(
+                json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                    "textDocument": {"uri": "untitled:a", "text": "let a = 1\n%p ${b}\n"},
+                }}),
+            ),
+            message(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": {
+                    "textDocument": {"uri": "untitled:a"}, "position": {"line": 0, "character": 4},
+                }}),
+            ),
+            message(json!({"jsonrpc": "2.0", "id": 3, "method": "unknown"})),
+            message(json!({"jsonrpc": "2.0", "id": 4, "method": "shutdown"})),
+            message(json!({"jsonrpc": "2.0", "method": "exit"})),
+        ]
+        .concat();
+
+        let mut server = Server {
+            output: Vec::new(),
+            env: None,
+            documents: HashMap::new(),
+            shutdown: false,
+        };
+        server.serve(&mut Cursor::new(input)).unwrap();
+
+        let mut output = Cursor::new(server.output);
+        let mut responses = Vec::new();
+        while let Some(response) = read_message(&mut output).unwrap() {
+            responses.push(response);
+        }
+
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["hoverProvider"],
+            true
+        );
+        assert_eq!(responses[1]["params"]["diagnostics"][0]["code"], "E0102");
+        assert_eq!(
+            responses[2]["result"]["contents"]["value"],
+            "`a`: a number\n\n```\n1\n```"
+        );
+        assert_eq!(responses[3]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[4]["result"], Value::Null);
+    }
+}
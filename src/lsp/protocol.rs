@@ -0,0 +1,182 @@
+// json-rpc over stdio as the language server protocol uses it: each message is a json body
+// after a `Content-Length` header. positions count utf-16 code units, astryx counts characters.
+
+use serde_json::Value;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// the next message, or `None` when the client has closed the stream
+pub(crate) fn read_message<R: BufRead>(reader: &mut R) -> std::io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| invalid_data("missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| invalid_data(&e.to_string()))
+}
+
+pub(crate) fn write_message<W: Write>(writer: &mut W, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// the path of a `file://` uri
+pub(crate) fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3).and_then(|hex| {
+            std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        });
+
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+pub(crate) fn path_to_uri(path: &Path) -> String {
+    let path = std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.to_path_buf());
+
+    let encoded: String = path
+        .to_string_lossy()
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+
+    format!("file://{}", encoded)
+}
+
+/// an lsp position for a 1-based line and character column in `text`
+pub(crate) fn position(text: &str, line: u32, column: usize) -> Value {
+    let source = text
+        .lines()
+        .nth(line.saturating_sub(1) as usize)
+        .unwrap_or("");
+
+    serde_json::json!({
+        "line": line.saturating_sub(1),
+        "character": utf16_length(source.chars().take(column.saturating_sub(1))),
+    })
+}
+
+/// an lsp range covering `length` characters from a 1-based line and column
+pub(crate) fn range(text: &str, line: u32, column: usize, length: usize) -> Value {
+    serde_json::json!({
+        "start": position(text, line, column),
+        "end": position(text, line, column + length),
+    })
+}
+
+/// the line of an lsp position and its character index (rather than utf-16 offset) in that line
+pub(crate) fn line_and_column<'a>(text: &'a str, position: &Value) -> Option<(&'a str, usize)> {
+    let line = text
+        .lines()
+        .nth(position["line"].as_u64()? as usize)
+        .unwrap_or("");
+    let offset = position["character"].as_u64()? as usize;
+
+    let mut units = 0;
+    let column = line
+        .chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= offset
+        })
+        .count();
+
+    Some((line, column))
+}
+
+fn utf16_length<I: Iterator<Item = char>>(chars: I) -> usize {
+    chars.map(char::len_utf16).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_messages() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &json!({"id": 1, "method": "shutdown"})).unwrap();
+        write_message(&mut buffer, &json!({"method": "exit"})).unwrap();
+
+        let mut reader = std::io::Cursor::new(buffer);
+        assert_eq!(read_message(&mut reader).unwrap().unwrap()["id"], 1);
+        assert_eq!(
+            read_message(&mut reader).unwrap().unwrap()["method"],
+            "exit"
+        );
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///home/me/my%20site/site.astryx"),
+            Some(PathBuf::from("/home/me/my site/site.astryx"))
+        );
+        assert_eq!(uri_to_path("untitled:1"), None);
+        assert_eq!(
+            path_to_uri(Path::new("/my site/a.md")),
+            "file:///my%20site/a.md"
+        );
+    }
+
+    #[test]
+    fn test_positions() {
+        let text = "let a = 1\n%p 🦀 ${a}";
+        assert_eq!(position(text, 2, 6), json!({"line": 1, "character": 6}));
+        assert_eq!(
+            line_and_column(text, &json!({"line": 1, "character": 6})),
+            Some(("%p 🦀 ${a}", 5))
+        );
+    }
+}
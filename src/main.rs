@@ -1,11 +1,20 @@
-use error::{display::display_error, AstryxError, AstryxResult};
-use models::{Site, State};
+use check::Format;
+use config::Config;
+use error::{display::terminal_error, AstryxError};
 use repl;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 mod build;
+mod cache;
+mod check;
+mod config;
+mod fmt;
+mod inspector;
+mod livereload;
+mod lsp;
+mod new;
+mod render;
 mod server;
 
 #[derive(StructOpt, Debug)]
@@ -14,6 +23,9 @@ struct Opt {
     /// Command
     #[structopt(subcommand)]
     command: Command,
+    /// Environment from astryx.toml to use, e.g. production
+    #[structopt(long, global = true)]
+    env: Option<String>,
 }
 
 #[derive(StructOpt, Debug)]
@@ -24,59 +36,125 @@ enum Command {
         /// Input file
         file: Option<String>,
         port: Option<u32>,
+        /// Address to listen on
+        #[structopt(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Directory of files served as they are
+        #[structopt(long = "static")]
+        static_dir: Option<String>,
     },
     /// build the project
     Build {
         /// Input file
         input: Option<String>,
         output: Option<String>,
+        /// How to print errors: text, or json for tools
+        #[structopt(long, default_value = "text")]
+        format: Format,
     },
     Check {
         /// Input file
         file: Option<String>,
+        /// How to print errors: text, or json for tools
+        #[structopt(long, default_value = "text")]
+        format: Format,
+    },
+    /// rewrite source files in the standard style
+    Fmt {
+        /// Files to format
+        files: Vec<String>,
+        /// Only list the files that aren't formatted, failing if there are any
+        #[structopt(long)]
+        check: bool,
+    },
+    /// start a language server for editors, on stdin and stdout
+    Lsp,
+    /// create a new project
+    New {
+        /// Directory to create the project in
+        dir: String,
+        /// One of blog, docs or portfolio
+        #[structopt(long, default_value = new::DEFAULT_TEMPLATE)]
+        template: String,
     },
-    New,
 }
 
 pub fn main() {
     match run() {
-        Ok(r) => println!("{}", r),
-        Err(e) => println!("{}", e),
+        Ok(r) => eprintln!("{}", r),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
 
 /// run cli commands
 fn run() -> Result<String, String> {
     let opt = Opt::from_args();
+    let env = opt.env;
+    // only the commands which use the config load it, so a broken astryx.toml doesn't stop the
+    // language server from starting and reporting it
+    let config = |format| {
+        Config::load(env.as_deref()).map_err(|e| check::fail(e, config::CONFIG_FILE, format))
+    };
 
     match opt.command {
-        Command::Serve { file, port } => {
-            let path = &file.unwrap_or(String::from("site.astryx"));
+        Command::Serve {
+            file,
+            port,
+            host,
+            static_dir,
+        } => {
+            let config = config(Format::Text)?;
+            let path = &file.unwrap_or_else(|| config.entry.clone());
+            let options = server::Options {
+                host,
+                port: port.unwrap_or(8888),
+                static_dirs: match static_dir {
+                    Some(dir) => vec![dir.into()],
+                    None => config.static_dirs.clone(),
+                },
+                output: config.output.clone(),
+            };
 
-            server::start(path.into(), port.unwrap_or(8888)).map_err(|e| display_error(&e, path))
+            server::start(path.into(), options, env).map_err(|e| terminal_error(&e, path))
         }
-        Command::Build { input, output } => {
-            let path = &input.unwrap_or(String::from("site.astryx"));
-            let file = std::fs::read_to_string(&path).expect(&format!("could not open {}", path));
+        Command::Build {
+            input,
+            output,
+            format,
+        } => {
+            let config = config(format)?;
+            let path = &input.unwrap_or_else(|| config.entry.clone());
+            let output = output.map_or_else(|| config.output.clone(), PathBuf::from);
+            let file = read(path, format)?;
 
-            println!("building: {}\n", &path);
-            build::build(&file, &path).map_err(|e| display_error(&e, path))
+            eprintln!("building: {}\n", &path);
+            match build::build(&file, path, &config, &output) {
+                Ok(()) => Ok(()),
+                Err(e) => check::report(&[e], path, format),
+            }
         }
-        Command::Check { file } => {
-            let path = &file.unwrap_or(String::from("site.astryx"));
-            let file = std::fs::read_to_string(&path).expect(&format!("could not open {}", path));
+        Command::Check { file, format } => {
+            let config = config(format)?;
+            let path = &file.unwrap_or_else(|| config.entry.clone());
+            let file = read(path, format)?;
 
-            println!("checking: {}\n", &path);
-            let state = Rc::new(RefCell::new(State::new()));
+            eprintln!("checking: {}\n", &path);
+            let errors = check::check(&file, path, &config, Default::default());
 
-            parser::run(&file, path)
-                .map_err(AstryxError::from)
-                .and_then(|nodes| interpreter::run(&nodes, state))
-                .map(Site::render)
-                .map(|_| println!("no errors."))
-                .map_err(|e| display_error(&e, path))
+            check::report(&errors, path, format).map(|_| eprintln!("no errors."))
+        }
+        Command::New { dir, template } => new::new_project(dir.as_ref(), &template)
+            .map_err(|e| format!("error creating new project: {}", e)),
+        Command::Fmt { mut files, check } => {
+            if files.is_empty() {
+                files.push(config(Format::Text)?.entry);
+            }
+            fmt::fmt(&files, check)
         }
-        Command::New => new_project().map_err(|e| format!("error creating new project: {:?}", e)),
+        Command::Lsp => lsp::run(env).map_err(|e| e.to_string()),
         Command::Repl => {
             repl::run();
             Ok(())
@@ -85,7 +163,10 @@ fn run() -> Result<String, String> {
     .map(|_| "\ndone.".to_string())
 }
 
-/// set up a new project in the current directory
-fn new_project<'a>() -> AstryxResult<()> {
-    unimplemented!()
+/// read the file a command works on, reporting it in `format` if it can't be
+fn read(path: &str, format: Format) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| {
+        let error = AstryxError::Generic(format!("could not open {}: {}", path, e));
+        check::fail(error, path, format)
+    })
 }
@@ -0,0 +1,158 @@
+// `astryx new`, which creates a project from one of the templates in `templates/`
+//
+// templates are compiled into the binary, so new projects don't need a network
+// connection or a copy of the repository.
+
+use error::{AstryxError, AstryxResult};
+use std::path::Path;
+
+pub(crate) const DEFAULT_TEMPLATE: &str = "blog";
+
+/// every template gets the same stylesheet
+const STYLE: (&str, &str) = ("public/style.css", include_str!("../templates/style.css"));
+
+struct Template {
+    name: &'static str,
+    /// paths relative to the project directory, and their contents
+    files: &'static [(&'static str, &'static str)],
+}
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        name: "blog",
+        files: &[
+            ("site.astryx", include_str!("../templates/blog/site.astryx")),
+            ("astryx.toml", include_str!("../templates/blog/astryx.toml")),
+            STYLE,
+            (
+                "posts/hello-world.md",
+                include_str!("../templates/blog/posts/hello-world.md"),
+            ),
+            (
+                "posts/writing-posts.md",
+                include_str!("../templates/blog/posts/writing-posts.md"),
+            ),
+        ],
+    },
+    Template {
+        name: "docs",
+        files: &[
+            ("site.astryx", include_str!("../templates/docs/site.astryx")),
+            ("astryx.toml", include_str!("../templates/docs/astryx.toml")),
+            STYLE,
+            (
+                "pages/introduction.md",
+                include_str!("../templates/docs/pages/introduction.md"),
+            ),
+            (
+                "pages/getting-started.md",
+                include_str!("../templates/docs/pages/getting-started.md"),
+            ),
+        ],
+    },
+    Template {
+        name: "portfolio",
+        files: &[
+            (
+                "site.astryx",
+                include_str!("../templates/portfolio/site.astryx"),
+            ),
+            (
+                "astryx.toml",
+                include_str!("../templates/portfolio/astryx.toml"),
+            ),
+            STYLE,
+            (
+                "projects/first-project.md",
+                include_str!("../templates/portfolio/projects/first-project.md"),
+            ),
+            (
+                "projects/second-project.md",
+                include_str!("../templates/portfolio/projects/second-project.md"),
+            ),
+        ],
+    },
+];
+
+/// create a project in `dir` from the template called `template`. the directory may
+/// already exist, but only if it's empty.
+pub(crate) fn new_project(dir: &Path, template: &str) -> AstryxResult<()> {
+    let template = TEMPLATES
+        .iter()
+        .find(|t| t.name == template)
+        .ok_or_else(|| {
+            AstryxError::Generic(format!(
+                "no template called {}, try one of: {}",
+                template,
+                TEMPLATES
+                    .iter()
+                    .map(|t| t.name)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))
+        })?;
+
+    if dir.exists() && (dir.is_file() || dir.read_dir()?.next().is_some()) {
+        return Err(AstryxError::Generic(format!(
+            "{} already exists and isn't an empty directory",
+            dir.display()
+        )));
+    }
+
+    for (path, contents) in template.files {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)?;
+    }
+
+    println!(
+        "created a new {} in {}\n\nto start a server:\n  cd {}\n  astryx serve",
+        template.name,
+        dir.display(),
+        dir.display()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_templates_parse() {
+        for template in TEMPLATES {
+            let (_, source) = template
+                .files
+                .iter()
+                .find(|(path, _)| *path == "site.astryx")
+                .unwrap();
+
+            assert!(
+                parser::run(source, "site.astryx").is_ok(),
+                "{} doesn't parse",
+                template.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_new_project() {
+        let dir = std::env::temp_dir().join(format!("astryx-new-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        new_project(&dir, "docs").unwrap();
+        assert!(dir.join("site.astryx").is_file());
+        assert!(dir.join("public/style.css").is_file());
+        assert!(dir.join("pages/introduction.md").is_file());
+
+        // never written over
+        assert!(new_project(&dir, "docs").is_err());
+        assert!(new_project(&dir.join("site.astryx"), "docs").is_err());
+        assert!(new_project(&dir.join("other"), "nope").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
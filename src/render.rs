@@ -0,0 +1,189 @@
+// renders a site across threads
+//
+// the interpreter's state and object tree are single threaded (`Rc`), so rather than
+// sharing them, every worker interprets the source with its own state and only renders
+// its share of the top level routes, along with the routes nested in them. code outside
+// of routes runs in every worker, but only the first keeps its output. a path can only
+// have one route, so the output doesn't depend on how routes are split up.
+
+use crate::config::Config;
+use error::{AstryxError, AstryxResult};
+use models::{Dependencies, Node, Object, Site, State};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::thread;
+
+const THREADS_VAR: &str = "ASTRYX_THREADS";
+
+/// interpret and render the source `file` read from `path`, skipping `fresh` routes.
+/// returns the site along with every input read.
+pub(crate) fn render(
+    file: &str,
+    path: &str,
+    config: &Config,
+    fresh: &HashSet<String>,
+) -> AstryxResult<(Site, Dependencies)> {
+    render_with_workers(file, path, config, fresh, workers())
+}
+
+fn render_with_workers(
+    file: &str,
+    path: &str,
+    config: &Config,
+    fresh: &HashSet<String>,
+    workers: usize,
+) -> AstryxResult<(Site, Dependencies)> {
+    let shards: Vec<AstryxResult<(Site, Dependencies)>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|index| {
+                scope.spawn(move || render_shard(file, path, config, fresh, index, workers))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+
+    let mut documents: HashMap<String, String> = HashMap::new();
+    let mut dependencies = Dependencies::default();
+
+    // errors outside of routes are the same in every worker. otherwise the first
+    // worker's error is reported, so it's the same from one run to the next.
+    for shard in shards {
+        let (site, shard_dependencies) = shard?;
+
+        for (route, document) in site.documents {
+            documents.entry(route).or_default().push_str(&document);
+        }
+        dependencies.merge(shard_dependencies)?;
+    }
+
+    Ok((Site { documents }, dependencies))
+}
+
+/// one per core, or `ASTRYX_THREADS`
+fn workers() -> usize {
+    std::env::var(THREADS_VAR)
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+fn render_shard(
+    file: &str,
+    path: &str,
+    config: &Config,
+    fresh: &HashSet<String>,
+    index: usize,
+    count: usize,
+) -> AstryxResult<(Site, Dependencies)> {
+    let state = Rc::new(RefCell::new(State::new()));
+    let dependencies = state.borrow().dependencies();
+    dependencies.borrow_mut().set_fresh(fresh.clone());
+    dependencies.borrow_mut().set_shard(index, count);
+    state.borrow().track_input(path);
+    config.bind(&mut state.borrow_mut())?;
+
+    let nodes = parser::run(file, path)
+        .map_err(AstryxError::from)
+        .and_then(|nodes| interpreter::run(&nodes, Rc::clone(&state)))?;
+
+    // output outside of routes would otherwise be repeated by every worker, so the others
+    // only render their routes, wherever they're nested
+    let nodes = match index {
+        0 => nodes,
+        _ => {
+            let mut pages = Vec::new();
+            for node in &nodes {
+                collect_pages(node, &mut pages);
+            }
+            pages
+        }
+    };
+
+    let site = Site::render(nodes);
+    let dependencies = dependencies.borrow().clone();
+
+    Ok((site, dependencies))
+}
+
+/// the outermost routes in `node`, each with everything nested in it
+fn collect_pages(node: &Node<Object>, pages: &mut Vec<Node<Object>>) {
+    match &*node.borrow() {
+        Object::HTMLPage(_) => return pages.push(node.clone()),
+        Object::Array(items) => items.iter().for_each(|item| collect_pages(item, pages)),
+        _ => {}
+    }
+
+    for child in node.children() {
+        collect_pages(&child, pages);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn documents(source: &str, workers: usize) -> AstryxResult<HashMap<String, String>> {
+        render_with_workers(
+            source,
+            "site.astryx",
+            &Config::default(),
+            &HashSet::new(),
+            workers,
+        )
+        .map(|(site, _)| site.documents)
+    }
+
+    #[test]
+    fn test_nested_routes() {
+        let source = "@route path=\"/\"\n  %ul\n    for name in [\"/a\", \"/b\", \"/c\"]\n      %li ${name}\n      @route path=name\n        %p ${name}\n@route path=\"/d\"\n  %p d\n  @route path=\"/d/e\"\n    %p e\n";
+
+        let single = documents(source, 1).unwrap();
+        let mut routes: Vec<&String> = single.keys().collect();
+        routes.sort();
+        assert_eq!(routes, vec!["/", "/a", "/b", "/c", "/d", "/d/e"]);
+
+        for workers in 2..5 {
+            assert_eq!(documents(source, workers).unwrap(), single);
+        }
+
+        // output next to routes inside a top level loop is only written once
+        let source = "%h1 site\nfor name in [\"/a\", \"/b\", \"/c\"]\n  %p ${name}\n  %div\n    %p x\n    @route path=name\n      %p ${name}\n";
+
+        let single = documents(source, 1).unwrap();
+        assert_eq!(
+            single["/"],
+            "<h1>site</h1><p>/a</p><div><p>x</p></div><p>/b</p><div><p>x</p></div><p>/c</p><div><p>x</p></div>"
+        );
+        assert_eq!(single["/b"], "<p>/b</p>");
+
+        for workers in 2..5 {
+            assert_eq!(documents(source, workers).unwrap(), single);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_routes() {
+        let source = "@route path=\"/a\"\n  %p a\n@route path=\"/b\"\n  @route path=\"/a\"\n";
+
+        for workers in 1..3 {
+            match documents(source, workers) {
+                Err(AstryxError::LocatedError(d)) => {
+                    assert_eq!(d.kind.code(), "E0111");
+                    assert_eq!(d.location.line, 4);
+                    assert_eq!(d.labels[0].0.line, 1);
+                }
+                result => panic!("expected a duplicate route, got {:?}", result.map(|_| ())),
+            }
+        }
+    }
+}
@@ -1,119 +1,467 @@
+use crate::cache::{RenderCache, BUILD_CACHE_FILE};
+use crate::{inspector, livereload};
 use error::{
-    display::{display_error, html_error_page},
+    display::{display_error, html_error_page, terminal_error},
     AstryxError, AstryxResult,
 };
-use models::{Site, State};
-use simple_server::{Server, StatusCode};
-use std::cell::RefCell;
+use models::Site;
+use simple_server::{Method, Request, ResponseBuilder, ResponseResult, Server, StatusCode};
+use std::collections::hash_map::DefaultHasher;
 use std::fs::read_to_string;
-use std::rc::Rc;
-
-pub(crate) fn start<'a>(path: String, port: u32) -> AstryxResult<()> {
-    let host = "127.0.0.1";
-    let port = port.to_string();
-
-    let mut server = Server::new(move |request, mut response| {
-        // info!("Request received. {} {}", request.method(), request.uri());
-        let request_path = request.uri().path();
-
-        match request_path {
-            "/__ast" => {
-                let ast = read_to_string(&path)
-                    .map_err(AstryxError::from)
-                    .map(|ref file| format!("{:#?}", parser::run(file, &path)));
-
-                match ast {
-                    Ok(page) => Ok(response.body(page.as_bytes().to_vec())?),
-                    Err(e) => Ok(response.body(display_error(&e, &path).as_bytes().to_vec())?),
-                }
-            }
-            // "/__program" => {
-            //     let file = read_to_string(&path)?;
-            //     let program = parser::run(&file)
-            //         .map_err(AstryxError::from)
-            //         .and_then(|nodes| {
-            //             interpreter::run(&nodes, Rc::new(RefCell::new(State::new())))
-            //                 .map_err(AstryxError::from)
-            //         });
-
-            //     match program {
-            //         Ok(prog) => Ok(response.body(
-            //             prog.iter()
-            //                 .map(|inst| format!("{:?}", inst))
-            //                 .collect::<Vec<String>>()
-            //                 .join("\n")
-            //                 .as_bytes()
-            //                 .to_vec(),
-            //         )?),
-            //         Err(e) => Ok(response.body(display_error(&e, &path).as_bytes().to_vec())?),
-            //     }
-            // }
-            // "/__pages" => {
-            //     // let file = read_to_string(&path)?;
-            //     // let body = match render(&file) {
-            //     //     Ok(project) => project
-            //     //         .pages
-            //     //         .iter()
-            //     //         .map(|(path, page)| {
-            //     //             format!(
-            //     //                 "<p>{}</p><pre style='background-color:#AAA;padding:10px'>{}</pre><hr>",
-            //     //                 path, page
-            //     //             )
-            //     //         })
-            //     //         .collect(),
-            //     //     Err(e) => {
-            //     //         response.status(StatusCode::INTERNAL_SERVER_ERROR);
-            //     //         let error_text = display_error(&e, &path);
-            //     //         println!("{}", error_text);
-
-            //     //         html_error_page(&error_text)
-            //     //     }
-            //     // };
-            //     let body = String::from("fixme");
-
-            //     Ok(response.body(body.as_bytes().to_vec())?)
-            // }
-            _ => {
-                println!("{} {}", request.method(), request_path);
-
-                // let file = read_to_string(&path)?;
-
-                let state = Rc::new(RefCell::new(State::new()));
-
-                let result = parser::run(&read_to_string(&path)?, &path)
-                    .map_err(AstryxError::from)
-                    .and_then(|nodes| interpreter::run(&nodes, state))
-                    .map(Site::render);
-
-                // if request_path.contains("svg") {
-                //     response.header("content-type", "image/svg+xml");
-                //     // return Ok(response.body(svgfile.as_bytes().to_vec())?);
-                // }
-
-                let body = match result {
-                    Ok(site) => match site.documents.get(request_path) {
-                        Some(page) => page.clone(),
-                        None => {
-                            response.status(StatusCode::NOT_FOUND);
-                            format!("<h1>404</h1><p>Path not found: {}<p>", request_path)
-                        }
-                    },
-                    Err(e) => {
-                        response.status(StatusCode::INTERNAL_SERVER_ERROR);
-                        let error_text = display_error(&e, &path);
-                        println!("{}", error_text);
-
-                        html_error_page(&error_text)
-                    }
-                };
-
-                Ok(response.body(body.as_bytes().to_vec())?)
-            }
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// the route rendered for paths which aren't found
+const NOT_FOUND_ROUTE: &str = "/404";
+
+/// content types by file extension, anything else is served as `application/octet-stream`
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("xml", "application/xml"),
+    ("rss", "application/rss+xml"),
+    ("atom", "application/atom+xml"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("ico", "image/x-icon"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("pdf", "application/pdf"),
+    ("wasm", "application/wasm"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+];
+
+pub(crate) struct Options {
+    pub host: String,
+    pub port: u32,
+    /// files served as they are, ahead of rendered pages. earlier directories win.
+    pub static_dirs: Vec<PathBuf>,
+    /// where `build` writes the site, which live reload doesn't watch
+    pub output: PathBuf,
+}
+
+/// what a request resolved to, before it's turned into a response
+struct Reply {
+    status: StatusCode,
+    content_type: &'static str,
+    body: Vec<u8>,
+    last_modified: Option<SystemTime>,
+    /// set when redirecting
+    location: Option<String>,
+}
+
+impl Reply {
+    fn new(status: StatusCode, content_type: &'static str, body: Vec<u8>) -> Self {
+        Reply {
+            status,
+            content_type,
+            body,
+            last_modified: None,
+            location: None,
         }
+    }
+
+    fn html(status: StatusCode, body: String) -> Self {
+        Reply::new(
+            status,
+            mime_type(Path::new("index.html")),
+            body.into_bytes(),
+        )
+    }
+
+    /// a temporary redirect, as browsers remember permanent ones long after routes change
+    fn redirect(location: String) -> Self {
+        Reply {
+            location: Some(location),
+            ..Reply::new(StatusCode::FOUND, "text/plain", Vec::new())
+        }
+    }
+}
+
+pub(crate) fn start(path: String, options: Options, env: Option<String>) -> AstryxResult<()> {
+    let Options {
+        host,
+        port,
+        static_dirs,
+        output,
+    } = options;
+    let reload_port = port + 1;
+
+    // builds write into the output directory, and mustn't set off another reload
+    let ignored = vec![output.join(BUILD_CACHE_FILE), output];
+    let cache = Arc::new(Mutex::new(RenderCache::new(env.clone())));
+    livereload::start(
+        path.clone(),
+        &host,
+        reload_port,
+        ignored,
+        Arc::clone(&cache),
+    )?;
+
+    let mut server = Server::new(move |request, response| {
+        println!("{} {}", request.method(), request.uri().path());
+
+        let reply = match *request.method() {
+            Method::GET | Method::HEAD => {
+                route(&request, &path, &static_dirs, env.as_deref(), &cache)
+            }
+            _ => Reply::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "text/plain",
+                b"method not allowed".to_vec(),
+            ),
+        };
+
+        let reply = match reply.content_type.starts_with("text/html") {
+            true => Reply {
+                body: livereload::inject(&String::from_utf8_lossy(&reply.body), reload_port)
+                    .into_bytes(),
+                ..reply
+            },
+            false => reply,
+        };
+
+        respond(&request, response, reply)
     });
 
-    server.set_static_directory("public");
+    // static files are served by `route`, to get the same headers as pages
+    server.dont_serve_static_files();
 
     println!("listening on http://{}:{}/", host, port);
-    server.listen(host, &port);
+    println!("inspector at http://{}:{}{}", host, port, inspector::PREFIX);
+    server.listen(&host, &port.to_string());
+}
+
+/// resolve a request to a static file, a rendered page or an error
+fn route(
+    request: &Request<Vec<u8>>,
+    path: &str,
+    static_dirs: &[PathBuf],
+    env: Option<&str>,
+    cache: &Mutex<RenderCache>,
+) -> Reply {
+    let request_path = request.uri().path();
+
+    if request_path == "/__ast" {
+        let ast = read_to_string(path)
+            .map_err(AstryxError::from)
+            .map(|ref file| format!("{:#?}", parser::run(file, path)));
+
+        let body = match ast {
+            Ok(ast) => ast,
+            Err(e) => display_error(&e, path),
+        };
+
+        return Reply::new(
+            StatusCode::OK,
+            "text/plain; charset=utf-8",
+            body.into_bytes(),
+        );
+    }
+
+    if let Some(page) = inspector::page(path, request_path, env) {
+        return Reply::html(StatusCode::OK, page);
+    }
+
+    if let Some(reply) = static_dirs
+        .iter()
+        .find_map(|dir| static_file(dir, request_path))
+    {
+        return reply;
+    }
+
+    let mut cache = cache.lock().unwrap();
+    let site = match cache.render(path) {
+        Ok(site) => site,
+        Err(e) => {
+            eprintln!("{}", terminal_error(&e, path));
+
+            return Reply::html(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                html_error_page(&display_error(&e, path)),
+            );
+        }
+    };
+
+    match find_route(site, request_path) {
+        Some((route, url)) if url == request_path => {
+            let content_type = mime_type(&Site::document_path(Path::new(""), route));
+            Reply::new(
+                StatusCode::OK,
+                content_type,
+                site.documents[route].clone().into_bytes(),
+            )
+        }
+        Some((_, url)) => Reply::redirect(match request.uri().query() {
+            Some(query) => format!("{}?{}", url, query),
+            None => url,
+        }),
+        None => not_found(site, request_path),
+    }
+}
+
+/// the route serving `request_path` along with its canonical url. requests may differ
+/// from it by a trailing slash or `index.html`, and are redirected.
+fn find_route<'a>(site: &'a Site, request_path: &str) -> Option<(&'a str, String)> {
+    let requested = normalise(request_path);
+
+    // routes may be written with or without a leading slash (or `./`, from globs)
+    let route = site
+        .documents
+        .keys()
+        .filter(|route| normalise(route) == requested)
+        // when both `/a` and `/a/` exist, each is served as requested
+        .min_by_key(|route| route.trim_start_matches("./") != request_path)?;
+
+    let url = match normalise(route) {
+        "" => String::from("/"),
+        path if route.ends_with('/') => format!("/{}/", path),
+        path => format!("/{}", path),
+    };
+
+    Some((route, url))
+}
+
+fn normalise(path: &str) -> &str {
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+
+    // only a whole `index.html` segment, so `/myindex.html` isn't `/my`
+    let path = match path {
+        "index.html" => "",
+        path => path.strip_suffix("/index.html").unwrap_or(path),
+    };
+
+    path.trim_matches('/')
+}
+
+fn not_found(site: &Site, request_path: &str) -> Reply {
+    let body = match site.documents.get(NOT_FOUND_ROUTE) {
+        Some(page) => page.clone(),
+        None => format!("<h1>404</h1><p>Path not found: {}<p>", request_path),
+    };
+
+    Reply::html(StatusCode::NOT_FOUND, body)
+}
+
+/// a file from the static directory, or its `index.html` for directories
+fn static_file(static_dir: &Path, request_path: &str) -> Option<Reply> {
+    let relative = PathBuf::from(request_path.trim_start_matches('/'));
+
+    // paths like `/../secret` don't leave the static directory
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let mut file = static_dir.join(relative);
+    if file.is_dir() {
+        file = file.join("index.html");
+    }
+
+    let body = std::fs::read(&file).ok()?;
+    let last_modified = std::fs::metadata(&file).and_then(|m| m.modified()).ok();
+
+    Some(Reply {
+        last_modified,
+        ..Reply::new(StatusCode::OK, mime_type(&file), body)
+    })
+}
+
+/// build the response for `reply`, answering conditional requests and `HEAD` without a body
+fn respond(
+    request: &Request<Vec<u8>>,
+    mut response: ResponseBuilder,
+    reply: Reply,
+) -> ResponseResult {
+    let etag = etag(&reply.body);
+    let last_modified = reply.last_modified.map(http_date);
+
+    response.status(reply.status);
+    response.header("content-type", reply.content_type);
+    // always revalidated, so edits show up straight away
+    response.header("cache-control", "no-cache");
+
+    if let Some(location) = &reply.location {
+        response.header("location", location.as_str());
+    }
+
+    if reply.status == StatusCode::METHOD_NOT_ALLOWED {
+        response.header("allow", "GET, HEAD");
+    }
+
+    if reply.status == StatusCode::OK {
+        response.header("etag", etag.as_str());
+        if let Some(last_modified) = &last_modified {
+            response.header("last-modified", last_modified.as_str());
+        }
+
+        if is_not_modified(request, &etag, last_modified.as_deref()) {
+            response.status(StatusCode::NOT_MODIFIED);
+            return Ok(response.body(Vec::new())?);
+        }
+    }
+
+    if request.method() == Method::HEAD {
+        response.header("content-length", reply.body.len());
+        return Ok(response.body(Vec::new())?);
+    }
+
+    Ok(response.body(reply.body)?)
+}
+
+/// whether the client's cached copy, identified by `If-None-Match` or
+/// `If-Modified-Since`, is still current
+fn is_not_modified(request: &Request<Vec<u8>>, etag: &str, last_modified: Option<&str>) -> bool {
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    // If-Modified-Since is ignored when If-None-Match is present
+    match header("if-none-match") {
+        Some(tags) => tags
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag),
+        // browsers send back the Last-Modified they were given, so an exact match will do
+        None => last_modified.is_some() && header("if-modified-since") == last_modified,
+    }
+}
+
+/// a strong validator for a response body
+fn etag(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:x}-{:016x}\"", body.len(), hasher.finish())
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    MIME_TYPES
+        .iter()
+        .find(|(e, _)| *e == extension)
+        .map_or("application/octet-stream", |(_, mime)| mime)
+}
+
+/// format a time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn http_date(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let days = secs / 86400;
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// the (year, month, day) of a count of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's algorithm, with eras of 400 years starting on March 1st
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(
+            http_date(UNIX_EPOCH + Duration::from_secs(784111777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            http_date(UNIX_EPOCH + Duration::from_secs(951782400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(
+            mime_type(Path::new("a/style.CSS")),
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(
+            mime_type(&Site::document_path(Path::new(""), "/posts")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(mime_type(Path::new("LICENSE")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_find_route() {
+        let site = Site {
+            documents: vec!["/", "/about", "/docs/", "posts/a.md", "/my"]
+                .into_iter()
+                .map(|route| (route.to_string(), String::new()))
+                .collect(),
+        };
+
+        let url = |path| find_route(&site, path).map(|(_, url)| url);
+
+        assert_eq!(url("/"), Some(String::from("/")));
+        assert_eq!(url("/index.html"), Some(String::from("/")));
+        assert_eq!(url("/about/"), Some(String::from("/about")));
+        assert_eq!(url("/about/index.html"), Some(String::from("/about")));
+        assert_eq!(url("/docs"), Some(String::from("/docs/")));
+        assert_eq!(url("/posts/a.md"), Some(String::from("/posts/a.md")));
+        assert_eq!(url("/missing"), None);
+        assert_eq!(url("/myindex.html"), None);
+        assert_eq!(url("/index.html/index.html"), None);
+    }
 }
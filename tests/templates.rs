@@ -0,0 +1,98 @@
+// every template builds into a page for each of its markdown files, and sites build into the
+// same pages whether they're rendered on one thread or several
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const TEMPLATES: &[&str] = &["blog", "docs", "portfolio"];
+
+fn astryx(dir: &Path, args: &[&str], threads: usize) {
+    let output = Command::new(env!("CARGO_BIN_EXE_astryx"))
+        .args(args)
+        .current_dir(dir)
+        .env("ASTRYX_THREADS", threads.to_string())
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "astryx {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// every file under `dir` with the extension `extension`, and its contents
+fn files(dir: &Path, extension: &str) -> BTreeMap<PathBuf, String> {
+    let mut found = BTreeMap::new();
+
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            found.extend(files(&path, extension));
+        } else if path.extension().and_then(|e| e.to_str()) == Some(extension) {
+            found.insert(path.clone(), std::fs::read_to_string(&path).unwrap());
+        }
+    }
+
+    found
+}
+
+#[test]
+fn test_templates_build() {
+    let root = std::env::temp_dir().join(format!("astryx-templates-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+
+    for template in TEMPLATES {
+        astryx(&root, &["new", template, "--template", template], 1);
+        let dir = root.join(template);
+
+        let pages = build_threaded(&dir);
+        let markdown = files(&dir, "md");
+        assert_eq!(pages.len(), markdown.len() + 1, "{} pages", template);
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_output_beside_routes() {
+    let dir = std::env::temp_dir().join(format!("astryx-beside-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // markup in a top level loop and element, next to the routes inside them
+    std::fs::write(
+        dir.join("site.astryx"),
+        "%h1 site\n%main\n  for name in [\"/a\", \"/b\", \"/c\"]\n    %p ${name}\n    @route path=name\n      %p ${name}\n",
+    )
+    .unwrap();
+
+    let pages = build_threaded(&dir);
+    assert_eq!(pages.len(), 4);
+    assert_eq!(
+        pages[&dir.join("build/index.html")],
+        "<h1>site</h1><main><p>/a</p><p>/b</p><p>/c</p></main>"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// build the site in `dir` on one thread then on four, checking they write the same pages
+fn build_threaded(dir: &Path) -> BTreeMap<PathBuf, String> {
+    astryx(dir, &["build"], 1);
+    let pages = files(&dir.join("build"), "html");
+
+    std::fs::remove_dir_all(dir.join("build")).unwrap();
+    astryx(dir, &["build"], 4);
+    assert_eq!(
+        files(&dir.join("build"), "html"),
+        pages,
+        "{}",
+        dir.display()
+    );
+
+    pages
+}